@@ -0,0 +1,102 @@
+use std::{collections::BTreeSet, path::PathBuf};
+
+use clap::{CommandFactory, ValueEnum};
+use rustyline::{
+    completion::Completer, highlight::Highlighter, hint::Hinter, validate::Validator, Context,
+    Helper,
+};
+
+use sles::solve::Method;
+
+use crate::Cli;
+
+/// Completes command names, their flags, `--method` values and the pronumerals of the current
+/// system.
+#[derive(Default)]
+pub struct ReplHelper {
+    /// The pronumerals used by the equations entered so far.
+    pub pronumerals: BTreeSet<String>,
+}
+
+impl ReplHelper {
+    fn candidates(&self, line: &str, word: &str) -> Vec<String> {
+        let mut words = line.split_whitespace();
+        let command = line
+            .trim_start()
+            .strip_prefix(':')
+            .and_then(|_| words.next())
+            .map(|command| command.trim_start_matches(':'));
+        let previous = line[..line.len() - word.len()].split_whitespace().last();
+
+        let options: Vec<String> = match (command, previous) {
+            // The command name itself.
+            (Some(_), None) => Cli::command()
+                .get_subcommands()
+                .map(|command| format!(":{}", command.get_name()))
+                .collect(),
+            (Some("help"), Some(_)) => Cli::command()
+                .get_subcommands()
+                .map(|command| command.get_name().to_string())
+                .collect(),
+            (Some(_), Some("--method")) => Method::value_variants()
+                .iter()
+                .filter_map(|method| method.to_possible_value())
+                .map(|value| value.get_name().to_string())
+                .collect(),
+            (Some(name), Some(_)) if word.starts_with('-') => Cli::command()
+                .find_subcommand(name)
+                .into_iter()
+                .flat_map(|command| command.get_arguments())
+                .filter_map(|arg| arg.get_long())
+                .map(|long| format!("--{long}"))
+                .collect(),
+            _ => self.pronumerals.iter().cloned().collect(),
+        };
+
+        options
+            .into_iter()
+            .filter(|option| option.starts_with(word))
+            .collect()
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let line = &line[..pos];
+        // A word runs back to the last space or operator, but a number in front of a pronumeral
+        // is a coefficient, so `2x` completes `x`. Commands and their flags are words too.
+        let command = line.trim_start().starts_with(':');
+        let start = line
+            .char_indices()
+            .rev()
+            .find(|(_, c)| !(c.is_alphanumeric() || *c == '_' || command && matches!(c, ':' | '-')))
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let start = start
+            + line[start..]
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(line.len() - start);
+
+        Ok((start, self.candidates(line, &line[start..])))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+impl Highlighter for ReplHelper {}
+impl Validator for ReplHelper {}
+impl Helper for ReplHelper {}
+
+/// Where the history is kept between sessions, creating its directory if need be.
+pub fn history_path() -> Option<PathBuf> {
+    let dir = dirs::data_dir()?.join("sles");
+    std::fs::create_dir_all(&dir).ok()?;
+    Some(dir.join("history.txt"))
+}
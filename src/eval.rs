@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+
+use ariadne::{Label, Report, ReportKind};
+use num_traits::ToPrimitive;
+use thiserror::Error;
+
+use crate::expr::{Constant, Expr, ExprKind, Op, Span};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{0} has no value")]
+    Unbound(String, Span),
+    #[error("Cannot divide by zero")]
+    DivisionByZero(Span),
+    #[error("The result is not a real number")]
+    Undefined(Span),
+    #[error("The result is too large to represent")]
+    Overflow(Span),
+}
+pub type Result<T> = core::result::Result<T, Error>;
+
+impl Error {
+    /// The part of the source that caused the error.
+    pub fn span(&self) -> Span {
+        match self {
+            Error::Unbound(_, span)
+            | Error::DivisionByZero(span)
+            | Error::Undefined(span)
+            | Error::Overflow(span) => span.clone(),
+        }
+    }
+
+    pub fn report(&self) -> Report<'static> {
+        let label = match self {
+            Error::Unbound(..) => "this pronumeral is not bound",
+            Error::DivisionByZero(_) => "this divisor is zero",
+            Error::Undefined(_) => "this has no real value",
+            Error::Overflow(_) => "this is too large",
+        };
+        Report::build(ReportKind::Error, (), self.span().start)
+            .with_message(self)
+            .with_label(Label::new(self.span()).with_message(label))
+            .finish()
+    }
+}
+
+/// Evaluates `expr`, with each pronumeral taking its value from `bindings`.
+///
+/// An equation evaluates to `lhs - rhs`, which is zero when the bindings satisfy it.
+pub fn evaluate(expr: &Expr, bindings: &BTreeMap<String, f64>) -> Result<f64> {
+    let value = match &expr.kind {
+        ExprKind::BinOp(lhs, op, rhs) => {
+            let lhs = evaluate(lhs, bindings)?;
+            let divisor = &rhs.span;
+            let rhs = evaluate(rhs, bindings)?;
+            match op {
+                Op::Add => lhs + rhs,
+                Op::Sub => lhs - rhs,
+                Op::Mul => lhs * rhs,
+                Op::Div if rhs == 0.0 => return Err(Error::DivisionByZero(divisor.clone())),
+                Op::Div => lhs / rhs,
+                Op::Pow if lhs == 0.0 && rhs < 0.0 => {
+                    return Err(Error::DivisionByZero(expr.span.clone()))
+                }
+                Op::Pow => lhs.powf(rhs),
+            }
+        }
+        ExprKind::Num(num) => num.to_f64().unwrap_or(f64::INFINITY),
+        ExprKind::Var(var) => bindings
+            .get(var)
+            .copied()
+            .ok_or_else(|| Error::Unbound(var.clone(), expr.span.clone()))?,
+        ExprKind::Constant(Constant::Pi) => std::f64::consts::PI,
+        ExprKind::Constant(Constant::E) => std::f64::consts::E,
+        ExprKind::Equation(lhs, rhs) => evaluate(lhs, bindings)? - evaluate(rhs, bindings)?,
+    };
+
+    if value.is_nan() {
+        return Err(Error::Undefined(expr.span.clone()));
+    }
+    if value.is_infinite() {
+        return Err(Error::Overflow(expr.span.clone()));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use logos::Logos;
+
+    use super::*;
+    use crate::{expr::parse_expr, token::Token};
+
+    fn eval(source: &str, bindings: &[(&str, f64)]) -> Result<f64> {
+        let expr = parse_expr(Token::lexer(source))
+            .unwrap_or_else(|_| panic!("failed to parse {source:?}"));
+        let bindings = bindings
+            .iter()
+            .map(|(var, value)| (var.to_string(), *value))
+            .collect();
+        evaluate(&expr, &bindings)
+    }
+
+    #[test]
+    fn operations() {
+        let bindings = [("x", 3.0), ("y", 0.5)];
+        assert_eq!(eval("x + y", &bindings).unwrap(), 3.5);
+        assert_eq!(eval("x - y", &bindings).unwrap(), 2.5);
+        assert_eq!(eval("2x y", &bindings).unwrap(), 3.0);
+        assert_eq!(eval("x / y", &bindings).unwrap(), 6.0);
+        assert_eq!(eval("x^2 - 4^y", &bindings).unwrap(), 7.0);
+        assert_eq!(eval("-(x + 1)", &bindings).unwrap(), -4.0);
+    }
+
+    #[test]
+    fn unbound() {
+        assert!(matches!(
+            eval("x + z", &[("x", 1.0)]),
+            Err(Error::Unbound(var, span)) if var == "z" && span == (4..5)
+        ));
+    }
+
+    #[test]
+    fn division_by_zero() {
+        assert!(matches!(
+            eval("1 / (x - 2)", &[("x", 2.0)]),
+            Err(Error::DivisionByZero(span)) if span == (4..11)
+        ));
+        assert!(matches!(eval("0^(-1)", &[]), Err(Error::DivisionByZero(_))));
+    }
+
+    #[test]
+    fn not_finite() {
+        assert!(matches!(eval("(-8)^0.5", &[]), Err(Error::Undefined(_))));
+        assert!(matches!(eval("10^400", &[]), Err(Error::Overflow(_))));
+        assert!(matches!(
+            eval("x * 10^300", &[("x", 1e10)]),
+            Err(Error::Overflow(_))
+        ));
+    }
+}
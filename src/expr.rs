@@ -1,21 +1,38 @@
+use std::{
+    collections::BTreeSet,
+    fmt::{Display, Formatter},
+};
+
 use ariadne::{Label, Report, ReportKind};
 use chumsky::error::SimpleReason;
 use chumsky::prelude::*;
 use chumsky::Stream;
 use logos::Lexer;
 
-use crate::token::{Float, Token};
+use num_traits::{One, Signed, Zero};
+
+use crate::scalar::Rational;
+use crate::token::{split_word, Token};
+
+/// A range of characters in the source text.
+pub type Span = std::ops::Range<usize>;
 
 #[derive(Debug, Clone)]
-pub enum Expr {
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
     BinOp(Box<Expr>, Op, Box<Expr>),
-    Num(f64),
-    Var(char),
+    Num(Rational),
+    Var(String),
     Constant(Constant),
     Equation(Box<Expr>, Box<Expr>),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Constant {
     Pi,
     E,
@@ -31,26 +48,59 @@ pub enum Op {
 }
 
 impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Expr {
+        Expr { kind, span }
+    }
+
+    /// Builds `lhs op rhs`, spanning from the start of `lhs` to the end of `rhs`.
+    pub fn bin_op(lhs: Expr, op: Op, rhs: Expr) -> Expr {
+        let span = lhs.span.start..rhs.span.end;
+        Expr::new(ExprKind::BinOp(Box::new(lhs), op, Box::new(rhs)), span)
+    }
+
+    /// Every pronumeral the expression uses.
+    pub fn pronumerals(&self) -> BTreeSet<String> {
+        fn pronumerals_inner(expr: &Expr, acc: &mut BTreeSet<String>) {
+            match &expr.kind {
+                ExprKind::BinOp(lhs, _, rhs) | ExprKind::Equation(lhs, rhs) => {
+                    pronumerals_inner(lhs, acc);
+                    pronumerals_inner(rhs, acc);
+                }
+                ExprKind::Var(var) => {
+                    acc.insert(var.clone());
+                }
+                ExprKind::Num(_) | ExprKind::Constant(_) => {}
+            }
+        }
+
+        let mut acc = BTreeSet::new();
+        pronumerals_inner(self, &mut acc);
+        acc
+    }
+
     pub fn terms(self) -> Vec<Expr> {
         let mut acc = Vec::new();
         fn terms_inner(expr: Expr, acc: &mut Vec<Expr>) {
-            match expr {
-                Expr::BinOp(lhs, Op::Add, rhs) => {
+            match expr.kind {
+                ExprKind::BinOp(lhs, Op::Add, rhs) => {
                     terms_inner(*lhs, acc);
                     terms_inner(*rhs, acc);
                 }
-                Expr::BinOp(lhs, Op::Sub, rhs) => {
+                ExprKind::BinOp(lhs, Op::Sub, rhs) => {
                     terms_inner(*lhs, acc);
 
                     let mut acc2 = Vec::new();
                     terms_inner(*rhs, &mut acc2);
 
-                    assert!(acc2.len() == 1, "The rhs should always be one expr");
-                    let first = acc2.remove(0);
-                    let expr = Expr::BinOp(Box::new(Expr::Num(0.0)), Op::Sub, Box::new(first));
-                    acc.push(expr);
+                    for term in acc2 {
+                        let zero = Expr::new(
+                            ExprKind::Num(Rational::zero()),
+                            term.span.start..term.span.start,
+                        );
+                        acc.push(Expr::bin_op(zero, Op::Sub, term));
+                    }
                 }
-                _ => acc.push(expr),
+                kind => acc.push(Expr::new(kind, expr.span)),
             }
         }
 
@@ -58,6 +108,83 @@ impl Expr {
 
         acc
     }
+
+    /// How tightly the expression binds, for deciding where brackets are needed.
+    fn precedence(&self) -> u8 {
+        match &self.kind {
+            ExprKind::Equation(..) => 0,
+            ExprKind::BinOp(_, op, _) => op.precedence(),
+            ExprKind::Num(num) if !num.is_integer() || num.is_negative() => 2,
+            ExprKind::Num(_) | ExprKind::Var(_) | ExprKind::Constant(_) => 4,
+        }
+    }
+}
+
+impl Op {
+    /// How tightly the operator binds, higher binding tighter.
+    fn precedence(&self) -> u8 {
+        match self {
+            Op::Add | Op::Sub => 1,
+            Op::Mul | Op::Div => 2,
+            Op::Pow => 3,
+        }
+    }
+}
+
+impl Display for Expr {
+    /// Writes the expression with standard spacing and only the brackets it needs, writing
+    /// coefficients next to what they multiply, like `2x`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        /// Writes `expr`, bracketed if it binds looser than `precedence`.
+        fn operand(f: &mut Formatter<'_>, expr: &Expr, precedence: u8) -> std::fmt::Result {
+            if expr.precedence() < precedence {
+                write!(f, "({expr})")
+            } else {
+                write!(f, "{expr}")
+            }
+        }
+
+        match &self.kind {
+            ExprKind::Equation(lhs, rhs) => write!(f, "{lhs} = {rhs}"),
+            ExprKind::BinOp(lhs, Op::Mul, rhs) => match &lhs.kind {
+                // A unary minus is parsed as multiplying by -1.
+                ExprKind::Num(num) if *num == -Rational::one() => {
+                    write!(f, "-")?;
+                    operand(f, rhs, 2)
+                }
+                ExprKind::Num(num) if num.is_integer() && !num.is_negative() => {
+                    if matches!(rhs.kind, ExprKind::Num(_)) {
+                        write!(f, "{num} * {rhs}")
+                    } else {
+                        write!(f, "{num}")?;
+                        operand(f, rhs, 3)
+                    }
+                }
+                _ => {
+                    operand(f, lhs, 2)?;
+                    write!(f, " * ")?;
+                    operand(f, rhs, 3)
+                }
+            },
+            ExprKind::BinOp(lhs, op, rhs) => {
+                let precedence = op.precedence();
+                operand(f, lhs, precedence)?;
+                match op {
+                    Op::Add => write!(f, " + ")?,
+                    Op::Sub => write!(f, " - ")?,
+                    Op::Mul => write!(f, " * ")?,
+                    Op::Div => write!(f, "/")?,
+                    Op::Pow => write!(f, "^")?,
+                }
+                // Every operator groups to the left, so an equal right operand needs brackets.
+                operand(f, rhs, precedence + 1)
+            }
+            ExprKind::Num(num) => write!(f, "{num}"),
+            ExprKind::Var(var) => write!(f, "{var}"),
+            ExprKind::Constant(Constant::Pi) => write!(f, "π"),
+            ExprKind::Constant(Constant::E) => write!(f, "e"),
+        }
+    }
 }
 
 fn handle_error(error: Simple<Token>) -> Report<'static> {
@@ -81,41 +208,53 @@ fn handle_error(error: Simple<Token>) -> Report<'static> {
     builder.finish()
 }
 
-pub fn parse(tokens: Lexer<Token>) -> Result<Expr, Vec<Report>> {
+/// Lexes the source into tokens, splitting words into pronumerals and constants.
+fn tokens(tokens: Lexer<Token>) -> Stream<'static, Token, Span, std::vec::IntoIter<(Token, Span)>> {
+    let end = tokens.source().len();
     let tokens = tokens
         .spanned()
         .filter_map(|(token, span)| token.ok().map(|token| (token, span)))
-        .collect::<Vec<_>>()
-        .into_iter()
-        .map(|(token, span)| (token, span.start..span.end));
+        .flat_map(|(token, span)| match token {
+            Token::Pronumeral(word) => split_word(&word, span),
+            token => vec![(token, span)],
+        })
+        .collect::<Vec<_>>();
 
-    let parser = recursive(|top_level| {
+    Stream::from_iter(end..end, tokens.into_iter())
+}
+
+#[allow(clippy::result_large_err)]
+fn expression() -> impl Parser<Token, Expr, Error = Simple<Token>> + Clone {
+    recursive(|top_level| {
         let atom = filter_map(|span, token: Token| {
-            if let Token::Number(Float(num)) = token {
-                Ok(Expr::Num(num))
+            if let Token::Number(num) = token {
+                Ok(Expr::new(ExprKind::Num(num), span))
             } else {
                 Err(Simple::custom(span, "not a number"))
             }
         })
         .or(filter_map(|span, token: Token| {
             if let Token::Pronumeral(var) = token {
-                Ok(Expr::Var(var))
+                Ok(Expr::new(ExprKind::Var(var), span))
             } else {
                 Err(Simple::custom(span, "not a pronumeral"))
             }
         }))
         .or(just(Token::LParen)
             .ignore_then(top_level)
-            .then_ignore(just(Token::RParen)))
-        .or(just(Token::Pi).to(Expr::Constant(Constant::Pi)))
-        .or(just(Token::E).to(Expr::Constant(Constant::E)));
+            .then_ignore(just(Token::RParen))
+            .map_with_span(|expr: Expr, span| Expr::new(expr.kind, span)))
+        .or(just(Token::Pi)
+            .map_with_span(|_, span| Expr::new(ExprKind::Constant(Constant::Pi), span)))
+        .or(just(Token::E)
+            .map_with_span(|_, span| Expr::new(ExprKind::Constant(Constant::E), span)));
         let exponentiation = atom
             .clone()
             .then(just(Token::Pow).to(Op::Pow).then(atom.clone()).repeated())
             .map(|(lhs, ops)| {
                 let mut acc = lhs;
                 for (op, rhs) in ops {
-                    acc = Expr::BinOp(Box::new(acc), op, Box::new(rhs));
+                    acc = Expr::bin_op(acc, op, rhs);
                 }
                 acc
             });
@@ -135,14 +274,16 @@ pub fn parse(tokens: Lexer<Token>) -> Result<Expr, Vec<Report>> {
                     .map(|(lhs, ops)| {
                         let mut acc = lhs;
                         for (op, rhs) in ops {
-                            acc = Expr::BinOp(Box::new(acc), op, Box::new(rhs));
+                            acc = Expr::bin_op(acc, op, rhs);
                         }
                         acc
                     }),
             )
-            .map(|(sub, rhs)| {
+            .map_with_span(|(sub, rhs), span: Span| {
                 if sub.is_some() {
-                    Expr::BinOp(Box::new(Expr::Num(-1.0)), Op::Mul, Box::new(rhs))
+                    let minus_one =
+                        Expr::new(ExprKind::Num(-Rational::one()), span.start..span.start + 1);
+                    Expr::bin_op(minus_one, Op::Mul, rhs)
                 } else {
                     rhs
                 }
@@ -160,24 +301,47 @@ pub fn parse(tokens: Lexer<Token>) -> Result<Expr, Vec<Report>> {
             .map(|(lhs, ops)| {
                 let mut acc = lhs;
                 for (op, rhs) in ops {
-                    acc = Expr::BinOp(Box::new(acc), op, Box::new(rhs));
+                    acc = Expr::bin_op(acc, op, rhs);
                 }
                 acc
             })
-    });
+    })
+}
 
-    let equation = parser
-        .clone()
+/// Parses an equation, `lhs = rhs`.
+#[allow(clippy::result_large_err)]
+pub fn parse(tokens: Lexer<Token>) -> Result<Expr, Vec<Report>> {
+    let equation = expression()
         .then_ignore(just(Token::Eq))
-        .then(parser)
-        .map(|(lhs, rhs)| Expr::Equation(Box::new(lhs), Box::new(rhs)));
+        .then(expression())
+        .then_ignore(end())
+        .map_with_span(|(lhs, rhs), span| {
+            Expr::new(ExprKind::Equation(Box::new(lhs), Box::new(rhs)), span)
+        });
+
+    equation
+        .parse(self::tokens(tokens))
+        .map_err(|errors| errors.into_iter().map(handle_error).collect::<Vec<_>>())
+}
 
-    let result = equation
-        .parse(Stream::from_iter(
-            0..ExactSizeIterator::len(&tokens),
-            tokens.into_iter(),
-        ))
-        .map_err(|errors| errors.into_iter().map(handle_error).collect::<Vec<_>>());
+/// Parses an expression on its own, without an `=`.
+#[allow(clippy::result_large_err)]
+pub fn parse_expr(tokens: Lexer<Token>) -> Result<Expr, Vec<Report>> {
+    expression()
+        .then_ignore(end())
+        .parse(self::tokens(tokens))
+        .map_err(|errors| errors.into_iter().map(handle_error).collect::<Vec<_>>())
+}
+
+/// Parses each source as an equation, for tests.
+#[cfg(test)]
+pub(crate) fn parse_equations(sources: &[&str]) -> Vec<Expr> {
+    use logos::Logos;
 
-    result
+    sources
+        .iter()
+        .map(|source| {
+            parse(Token::lexer(source)).unwrap_or_else(|_| panic!("failed to parse {source:?}"))
+        })
+        .collect()
 }
@@ -0,0 +1,508 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    fmt::{Display, Formatter},
+};
+
+use thiserror::Error;
+
+use crate::{
+    scalar::Scalar,
+    solve::{Parametric, SolutionSet},
+    standardform::StandardForm,
+};
+
+/// Values smaller than this next to the values they were computed from are treated as zero.
+pub const EPSILON: f64 = 1e-10;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("There are no equations to solve!")]
+    NoEquations,
+}
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// An elementary row operation on an augmented matrix. Rows are 1-based.
+#[derive(Debug, Clone)]
+pub enum RowOp<T> {
+    /// Swap two rows.
+    Swap(usize, usize),
+    /// Multiply a row by a nonzero scalar.
+    Scale(usize, T),
+    /// Add a multiple of the `source` row to the `target` row.
+    AddMultiple {
+        target: usize,
+        source: usize,
+        factor: T,
+    },
+}
+
+impl<T: Scalar> Display for RowOp<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RowOp::Swap(a, b) => write!(f, "R{a} ↔ R{b}"),
+            RowOp::Scale(row, factor) => write!(f, "R{row} → ({factor})R{row}"),
+            RowOp::AddMultiple {
+                target,
+                source,
+                factor,
+            } => {
+                let sign = if factor.is_negative() { "-" } else { "+" };
+                write!(f, "R{target} → R{target} {sign} ")?;
+                if !factor.abs().is_one() {
+                    write!(f, "({})", factor.abs())?;
+                }
+                write!(f, "R{source}")
+            }
+        }
+    }
+}
+
+/// The row operations performed during an elimination, and the augmented matrix after each one.
+#[derive(Debug, Clone)]
+pub struct Trace<T> {
+    pub variables: Vec<String>,
+    pub initial: Vec<Vec<T>>,
+    pub steps: Vec<(RowOp<T>, Vec<Vec<T>>)>,
+}
+
+impl<T: Scalar> Trace<T> {
+    fn render(&self, f: &mut Formatter<'_>, rows: &[Vec<T>]) -> std::fmt::Result {
+        let scales = (0..=self.variables.len())
+            .map(|col| largest(self.initial.iter().map(|row| &row[col])))
+            .collect::<Vec<_>>();
+        let size = self.variables.len().max(self.initial.len());
+        let cells = rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .zip(&scales)
+                    .map(|(value, scale)| {
+                        if value.is_negligible_next_to(scale, size) {
+                            "0".to_string()
+                        } else {
+                            value.to_string()
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let header = self
+            .variables
+            .iter()
+            .cloned()
+            .chain(["=".to_string()])
+            .collect::<Vec<_>>();
+        let widths = (0..header.len())
+            .map(|col| {
+                cells
+                    .iter()
+                    .map(|row| row[col].chars().count())
+                    .chain([header[col].chars().count()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect::<Vec<_>>();
+        let label_width = rows.len().to_string().len() + 1;
+
+        let line = |label: &str, row: &[String]| {
+            let (coefficients, constant) = row.split_at(row.len() - 1);
+            let coefficients = coefficients
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:>width$}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let width = widths[widths.len() - 1];
+            format!(
+                "{label:<label_width$}  {coefficients} | {:>width$}",
+                constant[0]
+            )
+        };
+
+        writeln!(f, "{}", line("", &header))?;
+        for (i, row) in cells.iter().enumerate() {
+            writeln!(f, "{}", line(&format!("R{}", i + 1), row))?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Scalar> Display for Trace<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.render(f, &self.initial)?;
+        for (op, rows) in &self.steps {
+            writeln!(f)?;
+            writeln!(f, "{op}")?;
+            self.render(f, rows)?;
+        }
+        Ok(())
+    }
+}
+
+/// Solves a system of equations in standard form using Gaussian elimination with partial pivoting.
+///
+/// The pronumerals are collected from every equation, so they may appear in any order,
+/// and a pronumeral missing from an equation is treated as having a coefficient of zero.
+/// With [`Rational`](crate::scalar::Rational) coefficients the elimination is exact.
+pub fn solve<T: Scalar>(equations: &[StandardForm<T>]) -> Result<SolutionSet<T>> {
+    let (variables, rows) = augment(equations)?;
+    Ok(classify(&variables, rows))
+}
+
+/// Solves a system like [`solve`], recording each row operation along the way.
+pub fn solve_with_steps<T: Scalar>(
+    equations: &[StandardForm<T>],
+) -> Result<(SolutionSet<T>, Trace<T>)> {
+    let (variables, rows) = augment(equations)?;
+    Ok(classify_with_steps(&variables, rows))
+}
+
+/// Builds the augmented matrix of a system, returning the pronumeral of each column alongside it.
+fn augment<T: Scalar>(equations: &[StandardForm<T>]) -> Result<(Vec<String>, Vec<Vec<T>>)> {
+    if equations.is_empty() {
+        return Err(Error::NoEquations);
+    }
+
+    let variables = equations
+        .iter()
+        .flat_map(|equation| equation.pronumerals().cloned())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    let rows = equations
+        .iter()
+        .map(|equation| {
+            let mut row = variables
+                .iter()
+                .map(|variable| {
+                    equation
+                        .terms
+                        .get(variable)
+                        .cloned()
+                        .unwrap_or_else(T::zero)
+                })
+                .collect::<Vec<_>>();
+            row.push(equation.constant.clone());
+            row
+        })
+        .collect::<Vec<_>>();
+
+    Ok((variables, rows))
+}
+
+/// Reduces an augmented system to reduced row echelon form and classifies its solutions by rank.
+///
+/// Each row is `[coefficients.., constant]`, with one coefficient per entry in `variables`.
+pub fn classify<T: Scalar>(variables: &[String], rows: Vec<Vec<T>>) -> SolutionSet<T> {
+    reduce(variables, rows, None)
+}
+
+/// Classifies a system like [`classify`], recording each row operation along the way.
+pub fn classify_with_steps<T: Scalar>(
+    variables: &[String],
+    rows: Vec<Vec<T>>,
+) -> (SolutionSet<T>, Trace<T>) {
+    let mut trace = Trace {
+        variables: variables.to_vec(),
+        initial: rows.clone(),
+        steps: Vec::new(),
+    };
+    let solution = reduce(variables, rows, Some(&mut trace));
+    (solution, trace)
+}
+
+/// The largest magnitude of any of the values.
+pub(crate) fn largest<'a, T: Scalar + 'a>(values: impl Iterator<Item = &'a T>) -> T {
+    values.map(T::abs).fold(
+        T::zero(),
+        |largest, value| {
+            if value > largest {
+                value
+            } else {
+                largest
+            }
+        },
+    )
+}
+
+fn reduce<T: Scalar>(
+    variables: &[String],
+    rows: Vec<Vec<T>>,
+    trace: Option<&mut Trace<T>>,
+) -> SolutionSet<T> {
+    let width = variables.len();
+    let size = width.max(rows.len());
+
+    // Pronumerals that cancelled out of every equation have a column of zeros.
+    let unused = (0..width)
+        .filter(|&col| rows.iter().all(|row| row[col].is_zero()))
+        .map(|col| variables[col].clone())
+        .collect();
+
+    // The largest magnitude in each column, including the constants. Values are compared with
+    // these rather than with a fixed tolerance, so the scale of the system does not matter.
+    let scales = (0..=width)
+        .map(|col| largest(rows.iter().map(|row| &row[col])))
+        .collect::<Vec<_>>();
+
+    let original = rows.clone();
+    let mut rows = rows;
+    let pivots = eliminate(&mut rows, &scales, trace);
+
+    // Every row past the rank has no coefficients left, so it must read 0 = 0.
+    if let Some(contradiction) = (pivots.len()..rows.len())
+        .find(|&i| !rows[i][width].is_negligible_next_to(&scales[width], size))
+    {
+        return SolutionSet::Inconsistent {
+            equations: origins(original, &scales, contradiction),
+        };
+    }
+
+    if pivots.len() == width {
+        let solution = variables
+            .iter()
+            .cloned()
+            .zip(rows.iter().map(|row| row[width].clone()))
+            .collect();
+        return SolutionSet::Unique(solution);
+    }
+
+    let free = (0..width)
+        .filter(|col| !pivots.contains(col))
+        .collect::<Vec<_>>();
+    let pivots = pivots
+        .iter()
+        .zip(&rows)
+        .map(|(&col, row)| {
+            // The pivot row was divided through by the pivot, which changed its scale.
+            let terms = free
+                .iter()
+                .filter(|&&free| {
+                    let scale = scales[free].clone() / scales[col].clone();
+                    !row[free].is_negligible_next_to(&scale, size)
+                })
+                .map(|&free| (variables[free].clone(), -row[free].clone()))
+                .collect::<BTreeMap<_, _>>();
+            let parametric = Parametric {
+                constant: row[width].clone(),
+                terms,
+            };
+            (variables[col].clone(), parametric)
+        })
+        .collect();
+
+    SolutionSet::Infinite {
+        free: free.into_iter().map(|col| variables[col].clone()).collect(),
+        unused,
+        pivots,
+    }
+}
+
+/// Reduces the coefficient columns of `rows` to reduced row echelon form, returning the column of each pivot
+/// in order. `scales` holds the largest magnitude in each column of the augmented matrix, and
+/// any columns past it are carried along.
+fn eliminate<T: Scalar>(
+    rows: &mut [Vec<T>],
+    scales: &[T],
+    mut trace: Option<&mut Trace<T>>,
+) -> Vec<usize> {
+    let width = scales.len() - 1;
+    let height = rows.len();
+    let size = width.max(height);
+
+    // Records an operation with a snapshot of the augmented matrix.
+    let mut record = |op, rows: &[Vec<T>]| {
+        if let Some(trace) = trace.as_deref_mut() {
+            let snapshot = rows.iter().map(|row| row[..=width].to_vec()).collect();
+            trace.steps.push((op, snapshot));
+        }
+    };
+
+    // Eliminate below each pivot, reducing to row echelon form.
+    let mut pivots = Vec::new();
+    for col in 0..width {
+        let row = pivots.len();
+        let Some(pivot) = (row..height)
+            .max_by(|&a, &b| {
+                (rows[a][col].abs())
+                    .partial_cmp(&rows[b][col].abs())
+                    .unwrap_or(Ordering::Equal)
+            })
+            .filter(|&pivot| !rows[pivot][col].is_negligible_next_to(&scales[col], size))
+        else {
+            continue;
+        };
+
+        if pivot != row {
+            rows.swap(row, pivot);
+            record(RowOp::Swap(row + 1, pivot + 1), rows);
+        }
+
+        let scale = rows[row][col].clone();
+        if !scale.is_one() {
+            for value in &mut rows[row] {
+                *value = value.clone() / scale.clone();
+            }
+            record(RowOp::Scale(row + 1, T::one() / scale), rows);
+        }
+
+        let pivot_row = rows[row].clone();
+        for i in row + 1..height {
+            subtract(rows, i, &pivot_row, col, row, &mut record);
+        }
+
+        pivots.push(col);
+    }
+
+    // Clear above each pivot, last first, so the rows being subtracted are already reduced.
+    for (row, &col) in pivots.iter().enumerate().rev() {
+        let pivot_row = rows[row].clone();
+        for i in 0..row {
+            subtract(rows, i, &pivot_row, col, row, &mut record);
+        }
+    }
+
+    pivots
+}
+
+/// Subtracts a multiple of `pivot_row`, the row at `source` with a one in `col`, from the row at
+/// `target` to leave a zero in `col`.
+fn subtract<T: Scalar>(
+    rows: &mut [Vec<T>],
+    target: usize,
+    pivot_row: &[T],
+    col: usize,
+    source: usize,
+    record: &mut impl FnMut(RowOp<T>, &[Vec<T>]),
+) {
+    let factor = rows[target][col].clone();
+    if factor.is_zero() {
+        return;
+    }
+    for (value, pivot_value) in rows[target].iter_mut().zip(pivot_row) {
+        *value = value.clone() - factor.clone() * pivot_value.clone();
+    }
+    let op = RowOp::AddMultiple {
+        target: target + 1,
+        source: source + 1,
+        factor: -factor,
+    };
+    record(op, rows);
+}
+
+/// The original equations (1-based) that the row at `contradiction` was built from, once `rows`
+/// are eliminated.
+///
+/// The elimination is repeated with an identity block appended, so each row records the multiple
+/// of each original equation it is made of. The pivots are chosen from the same values as before,
+/// so the row at `contradiction` is the same.
+fn origins<T: Scalar>(mut rows: Vec<Vec<T>>, scales: &[T], contradiction: usize) -> Vec<usize> {
+    let height = rows.len();
+    for (i, row) in rows.iter_mut().enumerate() {
+        row.extend((0..height).map(|j| if i == j { T::one() } else { T::zero() }));
+    }
+    eliminate(&mut rows, scales, None);
+
+    let multiples = &rows[contradiction][scales.len()..];
+    let scale = largest(multiples.iter());
+    multiples
+        .iter()
+        .enumerate()
+        .filter(|(_, multiple)| !multiple.is_negligible_next_to(&scale, height))
+        .map(|(i, _)| i + 1)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::standardform::parse_standard_forms;
+
+    #[test]
+    fn small_coefficients() {
+        let solution = solve(&parse_standard_forms::<f64>(&["0.000000000001x = 1"])).unwrap();
+        assert!(
+            matches!(solution, SolutionSet::Unique(solution) if (solution["x"] - 1e12).abs() < 1.0)
+        );
+
+        let solution = solve(&parse_standard_forms::<f64>(&[
+            "0.000000000001x + 0.000000000001y = 0.000000000001",
+        ]))
+        .unwrap();
+        assert!(matches!(
+            solution,
+            SolutionSet::Infinite { free, unused, .. } if free == ["y"] && unused.is_empty()
+        ));
+
+        let solution = solve(&parse_standard_forms::<f64>(&[
+            "0.000000000001x + 0.000000000001y = 0.000000000001",
+            "0.000000000002x + 0.000000000002y = 0.000000000001",
+        ]))
+        .unwrap();
+        assert!(matches!(
+            solution,
+            SolutionSet::Inconsistent { equations } if equations == [1, 2]
+        ));
+    }
+
+    #[test]
+    fn cancelled_pronumerals() {
+        let solution = solve(&parse_standard_forms::<f64>(&["x - x + y = 1", "z = 2"])).unwrap();
+        let SolutionSet::Infinite {
+            free,
+            unused,
+            pivots,
+        } = solution
+        else {
+            panic!("expected infinitely many solutions");
+        };
+        assert_eq!(free, ["x"]);
+        assert_eq!(unused, ["x"]);
+        assert_eq!(pivots["y"].to_string(), "1");
+        assert_eq!(pivots["z"].to_string(), "2");
+    }
+
+    #[test]
+    fn large_coefficients() {
+        let solution = solve(&parse_standard_forms::<f64>(&[
+            "1000000000000x + 1000000000000y = 1",
+            "2000000000000x + 2000000000000y = 3",
+            "x - y = 0",
+        ]))
+        .unwrap();
+        assert!(matches!(
+            solution,
+            SolutionSet::Inconsistent { equations } if equations == [1, 2]
+        ));
+    }
+
+    #[test]
+    fn nearly_dependent() {
+        let solution = solve(&parse_standard_forms::<f64>(&[
+            "x + y = 2",
+            "x + 1.00000000001y = 2.00000000001",
+        ]))
+        .unwrap();
+        assert!(matches!(
+            solution,
+            SolutionSet::Unique(solution)
+                if (solution["x"] - 1.0).abs() < 1e-4 && (solution["y"] - 1.0).abs() < 1e-4
+        ));
+    }
+
+    #[test]
+    fn contradiction_origins() {
+        let solution = solve(&parse_standard_forms::<f64>(&[
+            "x + y = 1",
+            "x + y + z = 3",
+            "2x + 2y = 5",
+        ]))
+        .unwrap();
+        assert!(matches!(
+            solution,
+            SolutionSet::Inconsistent { equations } if equations == [1, 3]
+        ));
+    }
+}
@@ -0,0 +1,265 @@
+use std::fmt::{Display, Formatter};
+
+use nalgebra::{DMatrix, MatrixXx1};
+use thiserror::Error;
+
+use crate::{gaussian::EPSILON, matrix::MatrixForm, solve::SolutionSet};
+
+/// A change bigger than this means the iteration is running away rather than converging.
+const DIVERGED: f64 = 1e100;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Iterative methods need as many equations as pronumerals")]
+    NotSquare,
+    #[error(
+        "{0} cannot be given an equation of its own with a nonzero coefficient for it, so it \
+         cannot be iterated on"
+    )]
+    ZeroDiagonal(String),
+    #[error("The relaxation factor must be strictly between 0 and 2, not {0}")]
+    InvalidRelaxation(f64),
+    #[error(
+        "The iteration diverged after {0} iterations, so the method does not converge for this \
+         system. It is guaranteed to if each pronumeral's coefficient in its own equation outweighs the rest of \
+         that equation"
+    )]
+    Diverged(usize),
+    #[error(
+        "The iteration did not converge within {0} iterations, the last one changed the solution by {1}"
+    )]
+    NotConverged(usize, f64),
+}
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// The rule used to improve the estimate each iteration.
+#[derive(Debug, Clone, Copy)]
+pub enum Scheme {
+    /// Update every pronumeral from the previous estimate.
+    Jacobi,
+    /// Update each pronumeral from the newest values, including those updated this iteration.
+    GaussSeidel,
+    /// Gauss-Seidel, scaling each update by the relaxation factor.
+    Sor,
+}
+
+/// When to stop iterating.
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    /// Stop once no pronumeral changes by more than this.
+    pub tolerance: f64,
+    pub max_iterations: usize,
+    /// How far successive over-relaxation moves each update, between 0 and 2.
+    pub relaxation: f64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            tolerance: EPSILON,
+            max_iterations: 1000,
+            relaxation: 1.25,
+        }
+    }
+}
+
+/// How much each iteration changed the solution, as the largest change to any pronumeral.
+#[derive(Debug, Clone)]
+pub struct History {
+    pub changes: Vec<f64>,
+}
+
+impl Display for History {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = self.changes.len().to_string().len().max("Iteration".len());
+        write!(f, "{:>width$}  Change", "Iteration")?;
+        for (i, change) in self.changes.iter().enumerate() {
+            write!(f, "\n{:>width$}  {change:e}", i + 1)?;
+        }
+        write!(f, "\nConverged after {} iterations.", self.changes.len())
+    }
+}
+
+/// Pairs each pronumeral with an equation to update it from, returning the equation for each
+/// pronumeral. Each equation prefers the pronumerals it has the largest coefficients for, and
+/// pairs are reassigned along augmenting paths until every pronumeral has one.
+///
+/// Fails with a pronumeral that cannot be paired if there is no way to pair them all.
+fn pair_equations(a: &DMatrix<f64>) -> core::result::Result<Vec<usize>, usize> {
+    /// Finds a pronumeral for `row`, taking one from another equation if that equation can be
+    /// paired with something else.
+    fn pair(
+        row: usize,
+        preferences: &[Vec<usize>],
+        visited: &mut [bool],
+        owners: &mut [Option<usize>],
+    ) -> bool {
+        for &col in &preferences[row] {
+            if visited[col] {
+                continue;
+            }
+            visited[col] = true;
+            if owners[col].is_none_or(|owner| pair(owner, preferences, visited, owners)) {
+                owners[col] = Some(row);
+                return true;
+            }
+        }
+        false
+    }
+
+    let preferences = a
+        .row_iter()
+        .map(|row| {
+            let scale = row.amax();
+            let mut cols = (0..row.len())
+                .filter(|&col| row[col].abs() > EPSILON * scale)
+                .collect::<Vec<_>>();
+            cols.sort_by(|&i, &j| row[j].abs().total_cmp(&row[i].abs()));
+            cols
+        })
+        .collect::<Vec<_>>();
+
+    let mut owners = vec![None; a.ncols()];
+    for row in 0..a.nrows() {
+        pair(row, &preferences, &mut vec![false; a.ncols()], &mut owners);
+    }
+    owners
+        .iter()
+        .enumerate()
+        .map(|(col, owner)| owner.ok_or(col))
+        .collect()
+}
+
+impl MatrixForm {
+    /// Solves the system iteratively, starting from zero, until no pronumeral changes by more
+    /// than the tolerance.
+    pub fn iterate(&self, scheme: Scheme, options: Options) -> Result<(SolutionSet, History)> {
+        let a = &self.coefficients;
+        let b = &self.constants;
+        if !a.is_square() {
+            return Err(Error::NotSquare);
+        }
+        let relaxation = options.relaxation;
+        if matches!(scheme, Scheme::Sor) && !(relaxation > 0.0 && relaxation < 2.0) {
+            return Err(Error::InvalidRelaxation(relaxation));
+        }
+        // Reorder the equations so the ith updates the ith pronumeral.
+        let order =
+            pair_equations(a).map_err(|i| Error::ZeroDiagonal(self.variables[i].clone()))?;
+        let a = &a.select_rows(&order);
+        let b = &b.select_rows(&order);
+
+        let n = a.nrows();
+        let mut x = MatrixXx1::<f64>::zeros(n);
+        let mut history = History {
+            changes: Vec::new(),
+        };
+
+        for iteration in 1..=options.max_iterations {
+            let previous = x.clone();
+            for i in 0..n {
+                // Jacobi only looks at the previous estimate, the others use updates as they come.
+                let source = match scheme {
+                    Scheme::Jacobi => &previous,
+                    Scheme::GaussSeidel | Scheme::Sor => &x,
+                };
+                let sum = (0..n)
+                    .filter(|&j| j != i)
+                    .map(|j| a[(i, j)] * source[j])
+                    .sum::<f64>();
+                let update = (b[i] - sum) / a[(i, i)];
+                x[i] = match scheme {
+                    Scheme::Sor => (1.0 - relaxation) * previous[i] + relaxation * update,
+                    Scheme::Jacobi | Scheme::GaussSeidel => update,
+                };
+            }
+
+            let change = (&x - &previous).amax();
+            history.changes.push(change);
+            if !change.is_finite() || change > DIVERGED {
+                return Err(Error::Diverged(iteration));
+            }
+            if change <= options.tolerance {
+                let solution = self
+                    .variables
+                    .iter()
+                    .cloned()
+                    .zip(x.iter().copied())
+                    .collect();
+                return Ok((SolutionSet::Unique(solution), history));
+            }
+        }
+
+        Err(Error::NotConverged(
+            options.max_iterations,
+            history.changes.last().copied().unwrap_or(0.0),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{matrix::parse_matrix, solve::Solution};
+
+    /// Checks the iteration found each value in `expected`.
+    fn assert_solves(result: Result<(SolutionSet, History)>, expected: &Solution) {
+        let Ok((SolutionSet::Unique(solution), _)) = result else {
+            panic!("expected a unique solution, got {result:?}");
+        };
+        assert_eq!(solution.len(), expected.len());
+        for (var, value) in solution {
+            assert!((value - expected[&var]).abs() < 1e-8, "{var} = {value}");
+        }
+    }
+
+    #[test]
+    fn equations_out_of_order() {
+        let matrix = parse_matrix(&["y = 1", "x = 2"]);
+        let expected = [("x".to_string(), 2.0), ("y".to_string(), 1.0)].into();
+        for scheme in [Scheme::Jacobi, Scheme::GaussSeidel, Scheme::Sor] {
+            assert_solves(matrix.iterate(scheme, Options::default()), &expected);
+        }
+    }
+
+    #[test]
+    fn generated_names() {
+        // x10 sorts before x2, so the coefficient matrix is not diagonally dominant as stored.
+        let n = 120;
+        let sources = (0..n)
+            .map(|i| {
+                let mut source = format!("4x{i}");
+                if i > 0 {
+                    source += &format!(" - x{}", i - 1);
+                }
+                if i + 1 < n {
+                    source += &format!(" - x{}", i + 1);
+                }
+                source + &format!(" = {}", i % 7)
+            })
+            .collect::<Vec<_>>();
+        let sources = sources.iter().map(String::as_str).collect::<Vec<_>>();
+        let matrix = parse_matrix(&sources);
+
+        let SolutionSet::Unique(expected) = matrix
+            .factorise()
+            .unwrap()
+            .solve(matrix.constants.as_slice())
+            .unwrap()
+        else {
+            panic!("expected a unique solution");
+        };
+        for scheme in [Scheme::Jacobi, Scheme::GaussSeidel, Scheme::Sor] {
+            assert_solves(matrix.iterate(scheme, Options::default()), &expected);
+        }
+    }
+
+    #[test]
+    fn no_pairing() {
+        let matrix = parse_matrix(&["x = 1", "3x = 3", "y + z = 2"]);
+        assert!(matches!(
+            matrix.iterate(Scheme::Jacobi, Options::default()),
+            Err(Error::ZeroDiagonal(_))
+        ));
+    }
+}
@@ -1,6 +1,13 @@
+pub mod eval;
 pub mod expr;
+pub mod gaussian;
+pub mod iterative;
 pub mod matrix;
+pub mod scalar;
 pub mod solve;
+pub mod sparse;
 pub mod standardform;
+pub mod symbolic;
 pub mod termlist;
 pub mod token;
+pub mod verify;
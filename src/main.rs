@@ -1,115 +1,436 @@
-use std::{collections::VecDeque, io::Write};
+mod completer;
+mod script;
 
-use ariadne::Source;
-use clap::{Parser, Subcommand};
+use std::{fmt::Display, path::PathBuf, process::ExitCode};
+
+use ariadne::{Label, Report, ReportKind, Source};
+use clap::{
+    error::{ContextKind, ErrorKind},
+    CommandFactory, Parser, Subcommand,
+};
 use color_eyre::{
     eyre::{bail, Context},
     Result,
 };
 use logos::Logos;
+use num_traits::{Signed, Zero};
+use rustyline::{error::ReadlineError, history::DefaultHistory, Editor};
 
 use sles::{
-    expr::{parse, Expr},
-    matrix::MatrixForm,
-    solve::{self, Method},
+    eval,
+    expr::{parse, parse_expr, Expr, ExprKind, Span},
+    iterative::Options,
+    matrix::{Diagnostics, Factorised},
+    scalar::{Rational, Scalar},
+    solve::{self, Method, Solution, SolutionSet},
     standardform::StandardForm,
     termlist::TermList,
     token::Token,
+    verify,
 };
 
+use completer::{history_path, ReplHelper};
+use script::Input;
+
+/// Solves systems of linear equations. Run without a command to enter them interactively.
+#[derive(Parser, Debug)]
+#[command(name = "sles", version)]
+struct Args {
+    #[clap(subcommand)]
+    mode: Option<Mode>,
+}
+#[derive(Subcommand, Debug)]
+enum Mode {
+    /// Solve a system of equations without entering the REPL.
+    ///
+    /// The equations are read one per line from FILE, or from stdin if FILE is -, or given
+    /// with -e. Blank lines are skipped, and a line `:include PATH` reads the equations in
+    /// another file. The solution is printed to stdout.
+    ///
+    /// Exits with 0 when the system is solved, 3 when an equation is invalid, 4 when the
+    /// system has no solution and 1 for any other error.
+    Solve {
+        /// The file of equations, or - for stdin.
+        #[clap(required_unless_present = "equations", conflicts_with = "equations")]
+        file: Option<PathBuf>,
+        /// An equation of the system. Repeat it for each equation.
+        #[clap(short = 'e', long = "equation", value_name = "EQUATION")]
+        equations: Vec<String>,
+        /// How to solve the system [default: matrix].
+        #[clap(long)]
+        method: Option<Method>,
+    },
+}
+
+/// The REPL's commands, typed with a `:` prefix.
 #[derive(Parser, Debug)]
+#[command(name = "", no_binary_name = true, disable_help_subcommand = true)]
 struct Cli {
     #[clap(subcommand)]
     subcommand: Command,
 }
 #[derive(Subcommand, Debug)]
 enum Command {
+    /// List the commands, or show the help for one of them.
+    Help {
+        /// The command to show the help for.
+        command: Option<String>,
+    },
+    /// Quit the program.
     Quit,
+    /// Solve the system of equations entered so far.
     Solve {
-        #[clap(long, default_value = "matrix")]
-        method: Method,
+        /// How to solve the system [default: matrix]. Substitution and elimination print
+        /// their working.
+        #[clap(long)]
+        method: Option<Method>,
+        /// Solve in exact rational arithmetic, printing fractions. Works with the general,
+        /// substitution and elimination methods.
+        #[clap(long)]
+        exact: bool,
+        /// Stop iterating once no pronumeral changes by more than this.
+        #[clap(long, default_value_t = Options::default().tolerance)]
+        tolerance: f64,
+        /// Give up iterating after this many iterations.
+        #[clap(long, default_value_t = Options::default().max_iterations)]
+        max_iterations: usize,
+        /// The relaxation factor for the SOR method, between 0 and 2.
+        #[clap(long, default_value_t = Options::default().relaxation)]
+        relaxation: f64,
+        /// Improve the solution by this many steps of iterative refinement, printing how the
+        /// residuals shrink. Works with the matrix method.
+        #[clap(long, default_value_t = 0)]
+        refine: usize,
+        /// Show each row operation of the elimination.
+        #[clap(long, conflicts_with = "method")]
+        steps: bool,
     },
+    /// Print the terms of each equation entered so far.
     Terms,
-    File,
+    /// List the equations entered so far, numbered.
+    List,
+    /// Delete an equation.
+    Delete {
+        /// The number of the equation, as shown by :list.
+        n: usize,
+    },
+    /// Replace an equation with a new one.
+    Edit {
+        /// The number of the equation, as shown by :list.
+        n: usize,
+        #[clap(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        equation: Vec<String>,
+    },
+    /// Delete every equation, to start a new system.
+    Clear,
+    /// Undo the last change to the equations.
+    Undo,
+    /// Solve the system of equations in files, one equation per line.
+    ///
+    /// A line `:include PATH` in a file reads the equations in another file in its place, so
+    /// a large system can be split across files. The path is relative to the including file.
+    File {
+        #[clap(required = true)]
+        paths: Vec<PathBuf>,
+        /// How to solve the system [default: matrix].
+        #[clap(long, conflicts_with = "load")]
+        method: Option<Method>,
+        /// Add the equations to those entered so far rather than solving them.
+        #[clap(long)]
+        load: bool,
+    },
+    /// Print the determinants Cramer's rule would use, without solving.
+    Det,
+    /// Solve the last system again with new constants.
+    ///
+    /// Give one constant for each equation. The factorisation of the coefficients is reused.
+    Rhs {
+        #[clap(required = true, allow_negative_numbers = true)]
+        constants: Vec<f64>,
+    },
+    /// Evaluate an expression using the last solution.
+    ///
+    /// The pronumerals in an expression such as `:eval 2x + y` take their values from the last
+    /// solution, or from `--with`.
+    Eval {
+        /// Give a pronumeral a value, such as `--with x=2`, overriding the last solution.
+        #[clap(long = "with", value_parser = parse_binding)]
+        bindings: Vec<(String, f64)>,
+        #[clap(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        expr: Vec<String>,
+    },
+    /// Check the last solution against the equations it solved.
+    ///
+    /// Substitutes the solution into each equation and prints its residual, lhs - rhs.
+    Verify {
+        /// Flag residuals larger than this, relative to the size of the terms in their equation.
+        #[clap(long, default_value_t = verify::TOLERANCE)]
+        tolerance: f64,
+    },
 }
 
 struct Repl {
     exprs: Vec<Expr>,
+    /// The text each expr was parsed from, for rendering errors.
+    sources: Vec<String>,
+    /// The equations and sources before each change, most recent last, for `undo`.
+    undo: Vec<(Vec<Expr>, Vec<String>)>,
+    /// The last system solved and its sources, kept so `rhs` can replace its constants.
+    last: Option<(Vec<Expr>, Vec<String>)>,
+    /// The factorisation of `last`, once `rhs` has needed it.
+    factorised: Option<Factorised>,
+    /// The values of the last unique or approximate solution, for `verify`.
+    solution: Option<Solution>,
 }
 impl Repl {
     fn run(&mut self, input: String) -> Result<()> {
-        let mut args = input.trim().split_whitespace().collect::<VecDeque<_>>();
-        args.push_front("placeholder");
-        let cli = Cli::try_parse_from(args);
-        if let Ok(cli) = cli {
+        let Some(command) = input.trim_start().strip_prefix(':') else {
+            return self.add_equation(input);
+        };
+        let cli = match Cli::try_parse_from(command.split_whitespace()) {
+            Ok(cli) => cli,
+            Err(err) => return command_error(err),
+        };
+
+        {
             match cli.subcommand {
-                // ":help" => {
-                //     println!("Enter any equation in standard form (ax + by = c)");
-                //     println!("When you are ready to solve, type :solve");
-                //     println!("Note, you must have the same number of equations entered as the number of pronumerals, otherwise it is unsolvable");
-                //     println!();
-                //     println!("Commands:");
-                //     println!("  :help  - Print this help");
-                //     println!("  :quit  - Quit the program");
-                //     println!("  :solve - Solve the system of equations");
-                //     println!("  :terms - Print the terms of the system of equations");
-                //     println!("  :file  - Solve the system of equations from a file");
-                //     println!("           (equation.txt)");
-                // }
+                Command::Help { command: None } => print_help(),
+                Command::Help {
+                    command: Some(name),
+                } => {
+                    let name = name.trim_start_matches(':');
+                    let mut cli = Cli::command();
+                    let Some(command) = cli.find_subcommand_mut(name) else {
+                        bail!("Unknown command :{name}. Type :help to list the commands.");
+                    };
+                    let help = command
+                        .clone()
+                        .bin_name(format!(":{name}"))
+                        .render_long_help();
+                    println!("{}", help.to_string().trim_end());
+                }
                 Command::Quit => {
                     std::process::exit(0);
                 }
-                Command::Solve { method } => {
+                Command::Solve {
+                    method,
+                    exact,
+                    steps,
+                    tolerance,
+                    max_iterations,
+                    relaxation,
+                    refine,
+                } => {
+                    if self.exprs.is_empty() {
+                        bail!("No equations to solve.");
+                    };
+                    if exact
+                        && !matches!(
+                            method,
+                            None | Some(
+                                Method::General | Method::Substitution | Method::Elimination
+                            )
+                        )
                     {
-                        if self.exprs.is_empty() {
+                        bail!("--exact only works with the general, substitution and elimination methods.");
+                    }
+                    if refine > 0 && !matches!(method, None | Some(Method::Matrix)) {
+                        bail!("--refine only works with the matrix method.");
+                    }
+
+                    let exprs = self.exprs.clone();
+                    let sources = self.sources.clone();
+                    self.last = Some((exprs.clone(), sources.clone()));
+                    self.factorised = None;
+                    self.solution = None;
+                    let spans = exprs
+                        .iter()
+                        .map(|expr| expr.span.clone())
+                        .collect::<Vec<_>>();
+
+                    if let Some(method @ (Method::Substitution | Method::Elimination)) = method {
+                        if exact {
+                            let (solution, working) = report_errors(
+                                solve::solve_with_working::<Rational>(exprs, method),
+                                &sources,
+                            )?;
+                            print_working(&working);
+                            self.print_solution(&solution, &spans, &sources)?;
+                        } else {
+                            let (solution, working) = report_errors(
+                                solve::solve_with_working::<f64>(exprs, method),
+                                &sources,
+                            )?;
+                            print_working(&working);
+                            self.print_solution(&solution, &spans, &sources)?;
+                        }
+                    } else if let Some(
+                        method @ (Method::Jacobi | Method::GaussSeidel | Method::Sor),
+                    ) = method
+                    {
+                        let options = Options {
+                            tolerance,
+                            max_iterations,
+                            relaxation,
+                        };
+                        let (solution, history) = report_errors(
+                            solve::solve_iteratively(exprs, method, options),
+                            &sources,
+                        )?;
+                        println!("{history}");
+                        println!();
+                        self.print_solution(&solution, &spans, &sources)?;
+                    } else if let Some(Method::Cramer) = method {
+                        let (solution, determinants) =
+                            report_errors(solve::solve_with_determinants(exprs), &sources)?;
+                        println!("{determinants}");
+                        println!();
+                        self.print_solution(&solution, &spans, &sources)?;
+                    } else if exact && steps {
+                        let (solution, trace) =
+                            report_errors(solve::solve_exact_with_steps(exprs), &sources)?;
+                        println!("{trace}");
+                        self.print_solution(&solution, &spans, &sources)?;
+                    } else if exact {
+                        let solution = report_errors(solve::solve_exact(exprs), &sources)?;
+                        self.print_solution(&solution, &spans, &sources)?;
+                    } else if steps {
+                        let (solution, trace) =
+                            report_errors(solve::solve_with_steps(exprs), &sources)?;
+                        println!("{trace}");
+                        self.print_solution(&solution, &spans, &sources)?;
+                    } else if let Some(method) =
+                        method.filter(|method| !matches!(method, Method::Matrix))
+                    {
+                        let solution =
+                            report_errors(solve::solve_with_method(exprs, method), &sources)?;
+                        self.print_solution(&solution, &spans, &sources)?;
+                    } else {
+                        let (solution, diagnostics) =
+                            report_errors(solve::solve_with_diagnostics(exprs, refine), &sources)?;
+                        if let Some(diagnostics) = diagnostics {
+                            println!("{diagnostics}");
+                            println!();
+                            warn_ill_conditioned(&solution, &diagnostics, refine == 0);
+                        }
+                        self.print_solution(&solution, &spans, &sources)?;
+                    }
+                }
+                Command::File {
+                    paths,
+                    method,
+                    load,
+                } => {
+                    let mut sources = Vec::new();
+                    for path in &paths {
+                        sources.extend(script::read_equations(path)?);
+                    }
+                    let exprs = sources
+                        .iter()
+                        .map(|source| parse_equation(source))
+                        .collect::<Result<Vec<_>>>()?;
+
+                    if load {
+                        let plural = if exprs.len() == 1 { "" } else { "s" };
+                        println!("Loaded {} equation{plural}.", exprs.len());
+                        self.checkpoint();
+                        self.exprs.extend(exprs);
+                        self.sources.extend(sources);
+                    } else {
+                        if exprs.is_empty() {
                             bail!("No equations to solve.");
                         };
+                        self.last = Some((exprs.clone(), sources.clone()));
+                        self.factorised = None;
+                        self.solution = None;
+                        let spans = exprs
+                            .iter()
+                            .map(|expr| expr.span.clone())
+                            .collect::<Vec<_>>();
 
-                        let exprs = std::mem::replace(&mut self.exprs, Vec::new());
+                        let solution = report_errors(
+                            solve::solve_with_method(exprs, method.unwrap_or(Method::Matrix)),
+                            &sources,
+                        )?;
+                        self.print_solution(&solution, &spans, &sources)?;
+                    }
+                }
+                Command::Rhs { constants } => {
+                    let Some((exprs, sources)) = &self.last else {
+                        bail!("No system has been solved yet.");
+                    };
+                    let factorised = match self.factorised.take() {
+                        Some(factorised) => factorised,
+                        None => report_errors(solve::factorise(exprs.clone()), sources)?,
+                    };
+
+                    if let Some(constant) = constants.iter().find(|c| !c.is_finite()) {
+                        bail!("{constant} is not a finite number.");
+                    }
 
-                        let solution = solve::solve_with_method(exprs, method)?;
+                    let solution = factorised.solve(&constants);
+                    self.factorised = Some(factorised);
+                    let solution = solution?;
 
-                        for (var, val) in solution {
-                            println!("{var} = {val}");
+                    // The last system is now the one with the new constants, so `verify` checks
+                    // the solution against it.
+                    let sources = exprs
+                        .iter()
+                        .zip(sources)
+                        .zip(constants)
+                        .map(|((expr, source), constant)| replace_constant(expr, source, constant))
+                        .collect::<Vec<_>>();
+                    let exprs = sources
+                        .iter()
+                        .map(|source| parse_equation(source))
+                        .collect::<Result<Vec<_>>>()?;
+                    let spans = exprs
+                        .iter()
+                        .map(|expr| expr.span.clone())
+                        .collect::<Vec<_>>();
+                    self.print_solution(&solution, &spans, &sources)?;
+                    self.last = Some((exprs, sources));
+                }
+                Command::Eval { bindings, expr } => {
+                    let source = expr.join(" ");
+                    let expr = match parse_expr(Token::lexer(&source)) {
+                        Ok(expr) => expr,
+                        Err(reports) => {
+                            for report in reports {
+                                report.eprint(Source::from(&source))?;
+                            }
+                            bail!("Failed to parse");
+                        }
+                    };
+
+                    let mut values = self.solution.clone().unwrap_or_default();
+                    values.extend(bindings);
+                    match eval::evaluate(&expr, &values) {
+                        Ok(value) => println!("{value}"),
+                        Err(err) => {
+                            err.report().eprint(Source::from(&source))?;
+                            bail!("Failed to evaluate {source:?}");
                         }
                     }
+                }
+                Command::Verify { tolerance } => {
+                    let (Some((exprs, sources)), Some(solution)) = (&self.last, &self.solution)
+                    else {
+                        bail!("There is no solution to verify.");
+                    };
 
-                    self.exprs = Vec::new();
+                    println!("{}", verify::verify(exprs, sources, solution, tolerance)?);
                 }
-                Command::File => {
-                    let input = std::fs::read_to_string("equation.txt")
-                        .wrap_err("Failed to read equation.txt")?;
-                    let lines = input.lines();
-                    let equations = lines
-                        .map(|line| {
-                            let tokens = Token::lexer(line);
-
-                            let expr = match parse(tokens) {
-                                Ok(expr) => expr,
-                                Err(reports) => {
-                                    for report in reports {
-                                        let source = Source::from(&input);
-                                        report.eprint(source).wrap_err(
-                                            "Failed to write error to stdout (double error!)",
-                                        )?;
-                                    }
-                                    bail!("Failed to parse");
-                                }
-                            };
-                            Ok(expr)
-                        })
-                        .collect::<Result<Vec<_>, _>>()?;
-
-                    let standard_eqs = equations
-                        .into_iter()
-                        .map(|expr| StandardForm::try_from(expr))
-                        .collect::<Result<Vec<_>, _>>()?;
-
-                    let matrix = MatrixForm::try_from(standard_eqs)?;
-                    let solutions = matrix.solve();
-
-                    for (var, val) in solutions {
-                        println!("{var} = {val}");
+                Command::Det => {
+                    if self.exprs.is_empty() {
+                        bail!("No equations to solve.");
+                    };
+
+                    let determinants =
+                        report_errors(solve::determinants(self.exprs.clone()), &self.sources)?;
+                    println!("{determinants}");
+                    if determinants.is_singular() {
+                        println!("The coefficient determinant is zero, so the system is singular.");
                     }
                 }
                 Command::Terms => {
@@ -117,59 +438,359 @@ impl Repl {
                         bail!("No equations to solve.");
                     };
 
-                    let exprs = std::mem::replace(&mut self.exprs, Vec::new());
-                    for expr in exprs {
-                        let Expr::Equation(lhs, rhs) = expr else {
+                    for (expr, source) in self.exprs.iter().zip(&self.sources) {
+                        let ExprKind::Equation(lhs, rhs) = expr.kind.clone() else {
                             unreachable!("expr is guaranteed to be an equation by parser")
                         };
 
-                        let lhs = TermList::from_expr(*lhs);
-                        let rhs = TermList::from_expr(*rhs);
-                        println!("{lhs:?} = {rhs:?}");
+                        let lists = TermList::from_expr(*lhs)
+                            .and_then(|lhs| Ok((lhs, TermList::from_expr(*rhs)?)));
+                        match lists {
+                            Ok((lhs, rhs)) => println!("{lhs} = {rhs}"),
+                            Err(err) => err.report().eprint(Source::from(source))?,
+                        }
                     }
-
-                    self.exprs = Vec::new();
                 }
-            }
-        } else {
-            let tokens = Token::lexer(&input);
-
-            let expr = match parse(tokens) {
-                Ok(expr) => expr,
-                Err(reports) => {
-                    for report in reports {
-                        let source = Source::from(&input);
-                        report.eprint(source)?;
+                Command::List => {
+                    if self.exprs.is_empty() {
+                        println!("No equations have been entered.");
+                    }
+                    for (i, source) in self.sources.iter().enumerate() {
+                        println!("({}) {}", i + 1, source.trim());
                     }
-                    bail!("Failed to parse");
                 }
-            };
-            self.exprs.push(expr);
+                Command::Delete { n } => {
+                    let i = self.index(n)?;
+                    self.checkpoint();
+                    self.exprs.remove(i);
+                    self.sources.remove(i);
+                }
+                Command::Edit { n, equation } => {
+                    let i = self.index(n)?;
+                    let source = equation.join(" ");
+                    let expr = parse_equation(&source)?;
+                    self.checkpoint();
+                    self.exprs[i] = expr;
+                    self.sources[i] = source;
+                }
+                Command::Clear => {
+                    self.checkpoint();
+                    self.exprs.clear();
+                    self.sources.clear();
+                }
+                Command::Undo => {
+                    let Some((exprs, sources)) = self.undo.pop() else {
+                        bail!("There is nothing to undo.");
+                    };
+                    self.exprs = exprs;
+                    self.sources = sources;
+                }
+            }
         }
         Ok(())
     }
+
+    /// Parses a line that is not a command as an equation and adds it to the system.
+    fn add_equation(&mut self, input: String) -> Result<()> {
+        // A command typed without its colon is not an equation, so point at the command instead.
+        let word = input.split_whitespace().next().unwrap_or_default();
+        if !input.contains('=') {
+            if let Some(command) = Cli::command().find_subcommand(word) {
+                bail!(
+                    "Did you mean :{}? Commands start with a colon.",
+                    command.get_name()
+                );
+            }
+        }
+        let expr = parse_equation(&input)?;
+        self.checkpoint();
+        self.exprs.push(expr);
+        self.sources.push(input);
+        Ok(())
+    }
+
+    /// Remembers the equations as they are, so the next change can be undone.
+    fn checkpoint(&mut self) {
+        self.undo.push((self.exprs.clone(), self.sources.clone()));
+    }
+
+    /// The index of equation `n`, counting from 1 as :list does.
+    fn index(&self, n: usize) -> Result<usize> {
+        if n == 0 || n > self.exprs.len() {
+            bail!(
+                "There is no equation {n}, there are {} equations.",
+                self.exprs.len()
+            );
+        }
+        Ok(n - 1)
+    }
+
+    /// Prints a solution, highlighting the equations that contradict each other if there is none,
+    /// and keeps its values for `verify`.
+    fn print_solution<T: Scalar>(
+        &mut self,
+        solution: &SolutionSet<T>,
+        spans: &[Span],
+        sources: &[String],
+    ) -> Result<()> {
+        println!("{solution}");
+        self.solution = solution.values();
+
+        if let SolutionSet::Inconsistent { equations } = solution {
+            report_contradiction(equations, spans, sources)?;
+        }
+        Ok(())
+    }
+}
+
+/// Warns that a unique solution may be inaccurate if its coefficients are ill-conditioned,
+/// suggesting `--refine` if `suggest_refine`.
+fn warn_ill_conditioned(solution: &SolutionSet, diagnostics: &Diagnostics, suggest_refine: bool) {
+    if !matches!(solution, SolutionSet::Unique(_)) || !diagnostics.is_ill_conditioned() {
+        return;
+    }
+    let hint = if suggest_refine {
+        " Try --refine to improve it."
+    } else {
+        ""
+    };
+    eprintln!(
+        "Warning: the condition number is {:.3e}, so about {:.0} digits of the solution may be wrong.{hint}",
+        diagnostics.condition_number,
+        diagnostics.digits_lost()
+    );
+}
+
+/// Highlights each of the `equations` (1-based) that contradict each other.
+fn report_contradiction(equations: &[usize], spans: &[Span], sources: &[String]) -> Result<()> {
+    for &i in equations {
+        let span = spans[i - 1].clone();
+        Report::build(ReportKind::Advice, (), span.start)
+            .with_message(format!("Equation {i}"))
+            .with_label(Label::new(span).with_message("this equation is part of the contradiction"))
+            .finish()
+            .eprint(Source::from(&sources[i - 1]))?;
+    }
+    Ok(())
+}
+
+/// Parses an equation, rendering any errors against its source.
+fn parse_equation(source: &str) -> Result<Expr> {
+    match parse(Token::lexer(source)) {
+        Ok(expr) => Ok(expr),
+        Err(reports) => {
+            for report in reports {
+                report.eprint(Source::from(source))?;
+            }
+            bail!("Failed to parse");
+        }
+    }
+}
+
+/// The source of an equation whose constant, in standard form, is changed to `constant`. The
+/// left side is kept as it was typed, and so is the right side if it has pronumerals, with
+/// constants added to make up the difference.
+fn replace_constant(expr: &Expr, source: &str, constant: f64) -> String {
+    /// Writes ` + value` or ` - value` to follow another term.
+    fn term(value: impl Display + Signed) -> String {
+        if value.is_negative() {
+            format!(" - {}", value.abs())
+        } else {
+            format!(" + {value}")
+        }
+    }
+
+    let ExprKind::Equation(lhs, rhs) = &expr.kind else {
+        unreachable!("parse only returns equations")
+    };
+    let lhs_source = &source[lhs.span.clone()];
+    let (kept, mut new_source) = if rhs.pronumerals().is_empty() {
+        let zero = Expr::new(ExprKind::Num(Rational::zero()), rhs.span.clone());
+        let kept = Expr::new(
+            ExprKind::Equation(lhs.clone(), Box::new(zero)),
+            expr.span.clone(),
+        );
+        (kept, format!("{lhs_source} = {constant}"))
+    } else {
+        let rhs_source = &source[rhs.span.clone()];
+        let new_source = format!("{lhs_source} = {rhs_source}{}", term(constant));
+        (expr.clone(), new_source)
+    };
+
+    // Take away the constant the kept sides already have, exactly unless it involves π or e.
+    match StandardForm::<Rational>::try_from(kept.clone()) {
+        Ok(equation) if !equation.constant.is_zero() => new_source += &term(-equation.constant),
+        Ok(_) => {}
+        Err(_) => {
+            if let Ok(equation) = StandardForm::<f64>::try_from(kept) {
+                new_source += &term(-equation.constant);
+            }
+        }
+    }
+    new_source
+}
+
+/// Prints the introduction and a summary of each command.
+fn print_help() {
+    println!("Enter each equation of the system on its own line, such as 2x + 3y = 7,");
+    println!("then type :solve to solve them. The equations are kept after solving, so they");
+    println!("can be changed and solved again; type :clear to start a new system.");
+    println!();
+    println!("Commands:");
+    let cli = Cli::command();
+    let width = cli
+        .get_subcommands()
+        .map(|command| command.get_name().len())
+        .max()
+        .unwrap_or(0);
+    for command in cli.get_subcommands() {
+        let about = command
+            .get_about()
+            .map(|about| about.to_string())
+            .unwrap_or_default();
+        println!("  :{:<width$}  {about}", command.get_name());
+    }
+    println!();
+    println!("Type :help <command> for more about a command.");
+}
+
+/// Explains why a line starting with `:` is not a valid command.
+fn command_error(err: clap::Error) -> Result<()> {
+    match err.kind() {
+        ErrorKind::DisplayHelp => {
+            print!("{err}");
+            Ok(())
+        }
+        ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand | ErrorKind::MissingSubcommand => {
+            print_help();
+            Ok(())
+        }
+        ErrorKind::InvalidSubcommand => {
+            let name = err
+                .get(ContextKind::InvalidSubcommand)
+                .map(ToString::to_string)
+                .unwrap_or_default();
+            bail!("Unknown command :{name}. Type :help to list the commands.");
+        }
+        _ => bail!("{}", err.render().to_string().trim_end()),
+    }
+}
+
+/// Parses a binding such as `x=2`.
+fn parse_binding(binding: &str) -> Result<(String, f64), String> {
+    let (var, value) = binding
+        .split_once('=')
+        .ok_or_else(|| format!("expected pronumeral=value, not {binding:?}"))?;
+    let value = value
+        .parse()
+        .map_err(|_| format!("{value:?} is not a number"))?;
+    Ok((var.to_string(), value))
+}
+
+/// Renders the error for an equation that could not be converted to standard form
+/// against its source.
+fn report_errors<T>(result: solve::Result<T>, sources: &[String]) -> Result<T> {
+    match result {
+        Ok(value) => Ok(value),
+        Err(solve::Error::ExprToStandardFormConversionFail(i, err)) => {
+            err.report().eprint(Source::from(&sources[i - 1]))?;
+            bail!("Failed to convert equation {i} to standard form");
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Prints each line of working, then a blank line before the solution.
+fn print_working(working: &[String]) {
+    for step in working {
+        println!("{step}");
+    }
+    println!();
 }
 
-fn main() -> Result<()> {
+/// Prints an error and the errors that caused it, one per line.
+fn print_error(err: &color_eyre::Report) {
+    eprintln!("{err}");
+    for cause in err.chain().skip(1) {
+        eprintln!("  {cause}");
+    }
+}
+
+fn main() -> Result<ExitCode> {
     color_eyre::install()?;
 
+    match Args::parse().mode {
+        Some(Mode::Solve {
+            file,
+            equations,
+            method,
+        }) => {
+            let input = match file {
+                Some(file) if file.as_os_str() == "-" => Input::Stdin,
+                Some(file) => Input::File(file),
+                None => Input::Equations(equations),
+            };
+            match script::solve(input, method.unwrap_or(Method::Matrix)) {
+                Ok(code) => Ok(code),
+                Err(err) => {
+                    print_error(&err);
+                    Ok(ExitCode::FAILURE)
+                }
+            }
+        }
+        None => {
+            repl()?;
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+/// Reads lines from the terminal until EOF, running each as a command or equation.
+fn repl() -> Result<()> {
     println!("System of Linear Equations Solver");
     println!("Type :help to learn more.");
 
-    let stdin = std::io::stdin();
+    let mut repl = Repl {
+        exprs: Vec::new(),
+        sources: Vec::new(),
+        undo: Vec::new(),
+        last: None,
+        factorised: None,
+        solution: None,
+    };
 
-    let mut repl = Repl { exprs: Vec::new() };
+    let mut editor = Editor::<ReplHelper, DefaultHistory>::new()?;
+    editor.set_helper(Some(ReplHelper::default()));
+    let history = history_path();
+    if let Some(history) = &history {
+        // There is no history to load the first time.
+        let _ = editor.load_history(history);
+    }
 
     loop {
-        print!("> ");
-
-        std::io::stdout().flush()?;
+        let input = match editor.readline("> ") {
+            Ok(input) => input,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        if input.trim().is_empty() {
+            continue;
+        }
 
-        let mut input = String::new();
-        stdin.read_line(&mut input)?;
+        editor.add_history_entry(input.as_str())?;
+        if let Some(history) = &history {
+            editor
+                .save_history(history)
+                .wrap_err("Failed to save the history")?;
+        }
 
         if let Err(err) = repl.run(input) {
-            eprintln!("{err}");
+            print_error(&err);
+        }
+
+        if let Some(helper) = editor.helper_mut() {
+            helper.pronumerals = repl.exprs.iter().flat_map(Expr::pronumerals).collect();
         }
     }
 }
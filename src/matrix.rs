@@ -1,26 +1,131 @@
-use std::collections::BTreeMap;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{Display, Formatter},
+};
 
-use nalgebra::{DMatrix, MatrixXx1};
+use nalgebra::{DMatrix, Dyn, MatrixXx1, LU, QR};
 use thiserror::Error;
 
-use crate::standardform::StandardForm;
+use crate::{
+    gaussian::{self, Trace},
+    solve::SolutionSet,
+    standardform::StandardForm,
+};
 
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("There are no equations to solve!")]
     NoEquations,
-    #[error("There are not the same number of pronumerals as equations, this makes the equation unsolveable")]
-    MismatchedPronumeralCount,
-    #[error("The pronumeral {0} is found in equation 1, but not in equation {1}")]
-    PronumeralNotCommon(char, usize),
+    #[error(
+        "There are fewer equations than pronumerals, so there is no single least-squares solution"
+    )]
+    TooFewEquations,
+    #[error(
+        "There are more equations than pronumerals, so there is no single minimum-norm solution"
+    )]
+    TooManyEquations,
+    #[error("The equations are not linearly independent")]
+    RankDeficient,
+    #[error("Determinants need as many equations as pronumerals")]
+    NotSquare,
+    #[error("The coefficient determinant is zero, so there is no unique solution")]
+    Singular,
+    #[error("Expected {0} constants, one for each equation, but got {1}")]
+    ConstantCount(usize, usize),
 }
 pub type Result<T> = core::result::Result<T, Error>;
 
 #[derive(Debug)]
 pub struct MatrixForm {
+    pub(crate) coefficients: DMatrix<f64>,
+    pub(crate) variables: MatrixXx1<String>,
+    pub(crate) constants: MatrixXx1<f64>,
+}
+
+/// The determinants used by Cramer's rule.
+#[derive(Debug, Clone)]
+pub struct Determinants {
+    /// The determinant of the coefficient matrix.
+    pub coefficient: f64,
+    /// For each pronumeral, the determinant with its column replaced by the constants.
+    pub replaced: BTreeMap<String, f64>,
+    /// Hadamard's bound on the size of the coefficient determinant, the product of the norms of
+    /// the coefficient columns.
+    pub bound: f64,
+}
+
+impl Determinants {
+    /// Whether the coefficient determinant is zero, up to rounding. It is compared with
+    /// [`Determinants::bound`], so the scale of the coefficients does not matter.
+    pub fn is_singular(&self) -> bool {
+        let size = self.replaced.len().max(1) as f64;
+        self.coefficient.abs() <= size * f64::EPSILON * self.bound
+    }
+}
+
+impl Display for Determinants {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "det(A) = {}", self.coefficient)?;
+        for (var, det) in &self.replaced {
+            write!(f, "\ndet(A_{var}) = {det}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Condition numbers above this mean the solution may have lost too many digits to trust.
+pub const ILL_CONDITIONED: f64 = 1e8;
+
+/// How accurate a solution is likely to be.
+#[derive(Debug, Clone)]
+pub struct Diagnostics {
+    /// The ratio of the largest to the smallest singular value of the coefficients, infinite if
+    /// they are singular. A solution can lose about `log10` of this many digits of accuracy.
+    pub condition_number: f64,
+    /// The norm of the residuals `Ax - b` of a unique solution, then after each step of iterative
+    /// refinement. Empty if there is no unique solution.
+    pub residual_norms: Vec<f64>,
+}
+
+impl Diagnostics {
+    pub fn is_ill_conditioned(&self) -> bool {
+        self.condition_number > ILL_CONDITIONED
+    }
+
+    /// Roughly how many significant digits the solution may have lost to rounding.
+    pub fn digits_lost(&self) -> f64 {
+        self.condition_number.log10().max(0.0)
+    }
+}
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Condition number: {:.3e}", self.condition_number)?;
+        for (i, norm) in self.residual_norms.iter().enumerate() {
+            match i {
+                0 => write!(f, "\nResidual norm: {norm:.3e}")?,
+                i => write!(f, "\nAfter refinement {i}: {norm:.3e}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A factorisation of a system's coefficients, computed once and applied to many sets of
+/// constants.
+#[derive(Debug, Clone)]
+pub struct Factorised {
     coefficients: DMatrix<f64>,
-    variables: MatrixXx1<char>,
-    constants: MatrixXx1<f64>,
+    variables: MatrixXx1<String>,
+    factorisation: Factorisation,
+}
+
+#[derive(Debug, Clone)]
+enum Factorisation {
+    /// A square system with a unique solution.
+    Lu(LU<f64, Dyn, Dyn>),
+    /// An overdetermined system with a single least-squares solution.
+    Qr(QR<f64, Dyn, Dyn>),
 }
 
 impl TryFrom<Vec<StandardForm>> for MatrixForm {
@@ -30,25 +135,21 @@ impl TryFrom<Vec<StandardForm>> for MatrixForm {
             return Err(Error::NoEquations);
         };
 
-        let mut variables = Vec::new();
         let mut coefficients = Vec::new();
         let mut constants = Vec::new();
 
-        for (variable, _) in &equations[0].terms {
-            variables.push(*variable);
-        }
-
-        if equations.len() != variables.len() {
-            return Err(Error::MismatchedPronumeralCount);
-        };
+        // Every pronumeral used by any equation, a missing pronumeral has a coefficient of zero.
+        let variables = equations
+            .iter()
+            .flat_map(|equation| equation.pronumerals().cloned())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
 
         for variable in &variables {
-            for (i, equation) in equations.iter().enumerate() {
-                let coeff = equation
-                    .terms
-                    .get(variable)
-                    .ok_or_else(|| Error::PronumeralNotCommon(*variable, i))?;
-                coefficients.push(*coeff);
+            for equation in &equations {
+                let coeff = equation.terms.get(variable).copied().unwrap_or(0.0);
+                coefficients.push(coeff);
             }
         }
         for equation in &equations {
@@ -56,7 +157,7 @@ impl TryFrom<Vec<StandardForm>> for MatrixForm {
         }
 
         let variables = MatrixXx1::from_vec(variables);
-        let coefficients = DMatrix::from_vec(variables.len(), equations.len(), coefficients);
+        let coefficients = DMatrix::from_vec(equations.len(), variables.len(), coefficients);
         let constants = MatrixXx1::from_vec(constants);
 
         Ok(MatrixForm {
@@ -67,24 +168,379 @@ impl TryFrom<Vec<StandardForm>> for MatrixForm {
     }
 }
 
+/// The numerical rank of a matrix. A matrix with no rows or columns has rank zero, where
+/// nalgebra would fail to take its SVD.
+///
+/// Each column is scaled to a largest entry of one first, and singular values are compared
+/// with the largest, so a column of small coefficients is not mistaken for zero. Singular values
+/// within the rounding error of the decomposition count as zero.
+fn rank(matrix: &DMatrix<f64>) -> usize {
+    if matrix.is_empty() {
+        return 0;
+    }
+
+    let mut scaled = matrix.clone();
+    for mut column in scaled.column_iter_mut() {
+        let largest = column.amax();
+        if largest > 0.0 {
+            column /= largest;
+        }
+    }
+    let singular_values = scaled.singular_values();
+    let size = matrix.nrows().max(matrix.ncols()) as f64;
+    let tolerance = size * f64::EPSILON * singular_values.max();
+    singular_values
+        .iter()
+        .filter(|&&value| value > tolerance)
+        .count()
+}
+
 impl MatrixForm {
-    pub fn solve(self) -> BTreeMap<char, f64> {
-        if !self.coefficients.is_invertible() {
-            println!("This system is not solvable. Approximating solution");
+    /// Solves the system, classifying it by comparing the rank of the coefficient matrix with
+    /// the rank of the augmented matrix.
+    pub fn solve(self) -> SolutionSet {
+        // Every term cancelled, so each equation reads `0 = constant`.
+        if self.variables.is_empty() {
+            return gaussian::classify(&[], self.augmented_rows());
         }
-        let inverse = self
+
+        let mut augmented = self
             .coefficients
-            .pseudo_inverse(0.000_000_000_01)
-            .expect("Epsilon guaranteed to be non negative");
+            .clone()
+            .insert_column(self.coefficients.ncols(), 0.0);
+        augmented.set_column(self.coefficients.ncols(), &self.constants);
+
+        let coefficient_rank = rank(&self.coefficients);
+        let augmented_rank = rank(&augmented);
+
+        if coefficient_rank == augmented_rank
+            && coefficient_rank == self.variables.len()
+            && self.coefficients.is_square()
+        {
+            if let Some(solution) = self.coefficients.clone().lu().solve(&self.constants) {
+                let solution = self
+                    .variables
+                    .iter()
+                    .cloned()
+                    .zip(solution.iter().copied())
+                    .collect();
+                return SolutionSet::Unique(solution);
+            }
+        }
+
+        gaussian::classify(self.variables.as_slice(), self.augmented_rows())
+    }
+
+    /// Solves the system like [`MatrixForm::solve`], along with the condition number of the
+    /// coefficients. A unique solution is improved by `refinements` steps of iterative refinement,
+    /// each solving for the correction that cancels out the residuals.
+    pub fn solve_with_diagnostics(self, refinements: usize) -> (SolutionSet, Diagnostics) {
+        let mut diagnostics = Diagnostics {
+            condition_number: self.condition_number(),
+            residual_norms: Vec::new(),
+        };
+
+        let full_rank = rank(&self.coefficients) == self.variables.len();
+        if self.coefficients.is_square() && full_rank {
+            let lu = self.coefficients.clone().lu();
+            if let Some(mut solution) = lu.solve(&self.constants) {
+                let mut residuals = &self.constants - &self.coefficients * &solution;
+                diagnostics.residual_norms.push(residuals.norm());
+                for _ in 0..refinements {
+                    let Some(correction) = lu.solve(&residuals) else {
+                        break;
+                    };
+                    solution += correction;
+                    residuals = &self.constants - &self.coefficients * &solution;
+                    diagnostics.residual_norms.push(residuals.norm());
+                }
+
+                let solution = self
+                    .variables
+                    .iter()
+                    .cloned()
+                    .zip(solution.iter().copied())
+                    .collect();
+                return (SolutionSet::Unique(solution), diagnostics);
+            }
+        }
+
+        (self.solve(), diagnostics)
+    }
+
+    /// The ratio of the largest to the smallest singular value of the coefficients, or infinity
+    /// if the smallest is zero. With no pronumerals there is no accuracy to lose, so it is one.
+    pub fn condition_number(&self) -> f64 {
+        if self.coefficients.is_empty() {
+            return 1.0;
+        }
+        let singular_values = self.coefficients.singular_values();
+        let smallest = singular_values.min();
+        if smallest == 0.0 {
+            f64::INFINITY
+        } else {
+            singular_values.max() / smallest
+        }
+    }
 
-        let solution = inverse * self.constants;
+    /// Solves the system by gauss-jordan elimination on the augmented matrix, recording each row
+    /// operation and the matrix it produces.
+    pub fn solve_with_steps(self) -> (SolutionSet, Trace<f64>) {
+        gaussian::classify_with_steps(self.variables.as_slice(), self.augmented_rows())
+    }
+
+    /// The rows of the augmented matrix `[coefficients | constants]`.
+    fn augmented_rows(&self) -> Vec<Vec<f64>> {
+        self.coefficients
+            .row_iter()
+            .zip(self.constants.iter())
+            .map(|(row, constant)| row.iter().copied().chain([*constant]).collect())
+            .collect()
+    }
+
+    /// The determinant of the coefficient matrix and, for each pronumeral, of the coefficient
+    /// matrix with that pronumeral's column replaced by the constants.
+    pub fn determinants(&self) -> Result<Determinants> {
+        if !self.coefficients.is_square() {
+            return Err(Error::NotSquare);
+        }
+
+        let replaced = self
+            .variables
+            .iter()
+            .enumerate()
+            .map(|(i, var)| {
+                let mut matrix = self.coefficients.clone();
+                matrix.set_column(i, &self.constants);
+                (var.clone(), matrix.determinant())
+            })
+            .collect();
+
+        Ok(Determinants {
+            coefficient: self.coefficients.determinant(),
+            replaced,
+            bound: self
+                .coefficients
+                .column_iter()
+                .map(|column| column.norm())
+                .product(),
+        })
+    }
+
+    /// Solves the system by Cramer's rule, each pronumeral being the ratio of its replaced-column
+    /// determinant to the coefficient determinant.
+    pub fn cramer(self) -> Result<(SolutionSet, Determinants)> {
+        let determinants = self.determinants()?;
+        if determinants.is_singular() {
+            return Err(Error::Singular);
+        }
+
+        let solution = determinants
+            .replaced
+            .iter()
+            .map(|(var, det)| (var.clone(), det / determinants.coefficient))
+            .collect();
+        Ok((SolutionSet::Unique(solution), determinants))
+    }
+
+    /// Factorises the coefficients, by LU if the system is square and by QR if it is
+    /// overdetermined, so it can be solved for other constants without starting again.
+    pub fn factorise(&self) -> Result<Factorised> {
+        if rank(&self.coefficients) < self.coefficients.ncols() {
+            return Err(Error::RankDeficient);
+        }
+
+        let factorisation = if self.coefficients.is_square() {
+            Factorisation::Lu(self.coefficients.clone().lu())
+        } else {
+            Factorisation::Qr(self.coefficients.clone().qr())
+        };
+        Ok(Factorised {
+            coefficients: self.coefficients.clone(),
+            variables: self.variables.clone(),
+            factorisation,
+        })
+    }
+
+    /// Finds the solution that minimises the sum of the squared residuals, using a QR factorisation.
+    ///
+    /// This is for overdetermined systems, which have more equations than pronumerals.
+    pub fn least_squares(self) -> Result<SolutionSet> {
+        if self.coefficients.nrows() < self.coefficients.ncols() {
+            return Err(Error::TooFewEquations);
+        }
+        if self.variables.is_empty() {
+            return Ok(self.approximate(MatrixXx1::zeros(0)));
+        }
+
+        let qr = self.coefficients.clone().qr();
+        let qtb = qr.q().transpose() * &self.constants;
+        let solution = qr
+            .r()
+            .solve_upper_triangular(&qtb)
+            .filter(|_| rank(&self.coefficients) == self.coefficients.ncols())
+            .ok_or(Error::RankDeficient)?;
+
+        Ok(self.approximate(solution))
+    }
+
+    /// Finds the solution with the smallest euclidean norm, using a QR factorisation of the transpose.
+    ///
+    /// This is for underdetermined systems, which have fewer equations than pronumerals.
+    pub fn minimum_norm(self) -> Result<SolutionSet> {
+        if self.coefficients.nrows() > self.coefficients.ncols() {
+            return Err(Error::TooManyEquations);
+        }
 
-        let mut solutions = BTreeMap::new();
+        // With A^T = QR, A = R^T Q^T, so solving R^T y = b gives x = Q y.
+        let qr = self.coefficients.transpose().qr();
+        let y = qr
+            .r()
+            .transpose()
+            .solve_lower_triangular(&self.constants)
+            .filter(|_| rank(&self.coefficients) == self.coefficients.nrows())
+            .ok_or(Error::RankDeficient)?;
+        let solution = qr.q() * y;
 
-        for (variable, value) in self.variables.into_iter().zip(solution.into_iter()) {
-            solutions.insert(*variable, *value);
+        Ok(self.approximate(solution))
+    }
+
+    fn approximate(self, solution: MatrixXx1<f64>) -> SolutionSet {
+        let residuals = (&self.coefficients * &solution - &self.constants)
+            .iter()
+            .copied()
+            .collect();
+        let solution = self
+            .variables
+            .iter()
+            .cloned()
+            .zip(solution.iter().copied())
+            .collect();
+        SolutionSet::Approximate {
+            solution,
+            residuals,
         }
+    }
+}
+
+impl Factorised {
+    /// Solves the system with `constants` in place of its own, one for each equation.
+    pub fn solve(&self, constants: &[f64]) -> Result<SolutionSet> {
+        if constants.len() != self.coefficients.nrows() {
+            return Err(Error::ConstantCount(
+                self.coefficients.nrows(),
+                constants.len(),
+            ));
+        }
+        let constants = MatrixXx1::from_column_slice(constants);
+
+        let solution = match &self.factorisation {
+            Factorisation::Lu(lu) => lu.solve(&constants).ok_or(Error::Singular)?,
+            Factorisation::Qr(qr) => qr
+                .r()
+                .solve_upper_triangular(&(qr.q().transpose() * &constants))
+                .ok_or(Error::RankDeficient)?,
+        };
+        let values = self
+            .variables
+            .iter()
+            .cloned()
+            .zip(solution.iter().copied())
+            .collect();
+
+        Ok(match self.factorisation {
+            Factorisation::Lu(_) => SolutionSet::Unique(values),
+            Factorisation::Qr(_) => SolutionSet::Approximate {
+                solution: values,
+                residuals: (&self.coefficients * &solution - &constants)
+                    .iter()
+                    .copied()
+                    .collect(),
+            },
+        })
+    }
+}
+
+/// Parses each source as an equation and builds the matrix form of the system, for tests.
+#[cfg(test)]
+pub(crate) fn parse_matrix(sources: &[&str]) -> MatrixForm {
+    MatrixForm::try_from(crate::standardform::parse_standard_forms(sources)).unwrap()
+}
 
-        solutions
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_coefficients() {
+        assert!(matches!(
+            parse_matrix(&["0.000000000001x = 1"]).solve(),
+            SolutionSet::Unique(solution) if (solution["x"] - 1e12).abs() < 1.0
+        ));
+        assert!(matches!(
+            parse_matrix(&["0.000000000001x = 0.000000000001"]).solve(),
+            SolutionSet::Unique(solution) if (solution["x"] - 1.0).abs() < 1e-9
+        ));
+    }
+
+    #[test]
+    fn cramer_scale() {
+        let small = parse_matrix(&[
+            "0.0001x + 0.0001y = 0.0002",
+            "0.0001x - 0.0001y = 0",
+            "0.0001z = 0.0001",
+        ]);
+        let Ok((SolutionSet::Unique(solution), _)) = small.cramer() else {
+            panic!("expected a unique solution");
+        };
+        for (var, expected) in [("x", 1.0), ("y", 1.0), ("z", 1.0)] {
+            assert!((solution[var] - expected).abs() < 1e-9);
+        }
+
+        let singular = parse_matrix(&["x + 2y = 3", "2x + 4y = 6"]);
+        assert!(matches!(singular.cramer(), Err(Error::Singular)));
+    }
+
+    #[test]
+    fn no_pronumerals() {
+        assert!(matches!(
+            parse_matrix(&["1 = 2"]).solve(),
+            SolutionSet::Inconsistent { equations } if equations == [1]
+        ));
+        assert!(matches!(
+            parse_matrix(&["x = x + 1", "0 = 0"]).solve(),
+            SolutionSet::Inconsistent { equations } if equations == [1]
+        ));
+        assert!(matches!(
+            parse_matrix(&["0 = 0"]).solve(),
+            SolutionSet::Unique(solution) if solution.is_empty()
+        ));
+        assert!(matches!(
+            parse_matrix(&["x = x"]).solve(),
+            SolutionSet::Infinite { free, unused, .. } if free == ["x"] && unused == ["x"]
+        ));
+        let (solution, diagnostics) = parse_matrix(&["1 = 2"]).solve_with_diagnostics(1);
+        assert!(matches!(solution, SolutionSet::Inconsistent { .. }));
+        assert_eq!(diagnostics.condition_number, 1.0);
+        assert!(matches!(
+            parse_matrix(&["1 = 2"]).least_squares(),
+            Ok(SolutionSet::Approximate { solution, residuals })
+                if solution.is_empty() && residuals == [-1.0]
+        ));
+    }
+
+    #[test]
+    fn nearly_dependent() {
+        let sources = ["x + y = 2", "x + 1.00000000001y = 2.00000000001"];
+        let Ok((SolutionSet::Unique(cramer), _)) = parse_matrix(&sources).cramer() else {
+            panic!("expected a unique solution");
+        };
+        let SolutionSet::Unique(solution) = parse_matrix(&sources).solve() else {
+            panic!("expected a unique solution");
+        };
+        for solution in [solution, cramer] {
+            assert!((solution["x"] - 1.0).abs() < 1e-4, "{solution:?}");
+            assert!((solution["y"] - 1.0).abs() < 1e-4, "{solution:?}");
+        }
     }
 }
@@ -0,0 +1,55 @@
+use std::fmt::{Debug, Display};
+
+use num_rational::BigRational;
+use num_traits::{Num, Signed, ToPrimitive, Zero};
+
+use crate::{standardform::Error, termlist::Term};
+
+/// An arbitrary precision fraction, used for exact arithmetic.
+pub type Rational = BigRational;
+
+/// A number the solvers can do arithmetic with, either an `f64` or an exact [`Rational`].
+pub trait Scalar: Num + Signed + Clone + PartialOrd + Debug + Display {
+    /// The coefficient of a term, or why it cannot be represented.
+    fn from_term(term: &Term) -> Result<Self, Error>;
+    /// Whether this is small enough next to `scale`, the size of the values it was computed
+    /// from, to be rounding error from eliminating in a system of `size` equations or
+    /// pronumerals. Such values are treated as zero.
+    fn is_negligible_next_to(&self, scale: &Self, size: usize) -> bool;
+    /// The nearest `f64`.
+    fn approximate(&self) -> f64;
+}
+
+impl Scalar for f64 {
+    fn from_term(term: &Term) -> Result<Self, Error> {
+        let value = term.get_approximate_coefficient();
+        // Coefficients outside the range of an f64 would overflow to infinity or round to zero.
+        if !value.is_finite() || (value == 0.0 && !term.coeff.is_zero()) {
+            return Err(Error::OutOfRange(term.span.clone()));
+        }
+        Ok(value)
+    }
+
+    fn is_negligible_next_to(&self, scale: &Self, size: usize) -> bool {
+        self.abs() <= size.max(1) as f64 * f64::EPSILON * scale.abs()
+    }
+
+    fn approximate(&self) -> f64 {
+        *self
+    }
+}
+
+impl Scalar for Rational {
+    fn from_term(term: &Term) -> Result<Self, Error> {
+        term.get_exact_coefficient()
+            .ok_or_else(|| Error::InexactTerm(term.span.clone()))
+    }
+
+    fn is_negligible_next_to(&self, _scale: &Self, _size: usize) -> bool {
+        self.is_zero()
+    }
+
+    fn approximate(&self) -> f64 {
+        self.to_f64().unwrap_or(f64::NAN)
+    }
+}
@@ -0,0 +1,239 @@
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use ariadne::Source;
+use color_eyre::{
+    eyre::{bail, Context},
+    Result,
+};
+
+use sles::solve::{self, Method, SolutionSet};
+
+use crate::{parse_equation, report_contradiction, warn_ill_conditioned};
+
+/// The system was solved, though there may be infinitely many solutions.
+const SOLVED: u8 = 0;
+/// An equation could not be parsed or is not linear.
+const INVALID: u8 = 3;
+/// The equations contradict each other.
+const INCONSISTENT: u8 = 4;
+
+/// Where `sles solve` reads its equations from.
+pub enum Input {
+    File(PathBuf),
+    Stdin,
+    Equations(Vec<String>),
+}
+
+impl Input {
+    /// The equations, skipping blank lines and following includes.
+    fn lines(self) -> Result<Vec<String>> {
+        match self {
+            Input::File(path) => read_equations(&path),
+            Input::Stdin => {
+                let mut text = String::new();
+                std::io::stdin()
+                    .read_to_string(&mut text)
+                    .wrap_err("Failed to read stdin")?;
+                let mut equations = Vec::new();
+                read_lines(&text, Path::new("."), &mut Vec::new(), &mut equations)?;
+                Ok(equations)
+            }
+            Input::Equations(equations) => Ok(equations),
+        }
+    }
+}
+
+/// Reads the equations in a file, one per line, skipping blank lines.
+///
+/// A line `:include PATH` reads the equations in another file in its place, with `PATH`
+/// relative to the file that includes it.
+pub fn read_equations(path: &Path) -> Result<Vec<String>> {
+    let mut equations = Vec::new();
+    read_file(path, &mut Vec::new(), &mut equations)?;
+    Ok(equations)
+}
+
+/// Reads the equations in `path` into `equations`. `including` holds the files that are part
+/// way through being read, so a file that includes itself can be caught.
+fn read_file(path: &Path, including: &mut Vec<PathBuf>, equations: &mut Vec<String>) -> Result<()> {
+    let text = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+    let canonical = path
+        .canonicalize()
+        .wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+    if including.contains(&canonical) {
+        bail!("{} includes itself", path.display());
+    }
+
+    including.push(canonical);
+    let dir = path.parent().unwrap_or(Path::new("."));
+    read_lines(&text, dir, including, equations)
+        .wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+    including.pop();
+    Ok(())
+}
+
+/// Reads the equations in `text`, with includes relative to `dir`.
+fn read_lines(
+    text: &str,
+    dir: &Path,
+    including: &mut Vec<PathBuf>,
+    equations: &mut Vec<String>,
+) -> Result<()> {
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        match line.trim().strip_prefix(":include") {
+            Some(path) if path.starts_with(char::is_whitespace) => {
+                read_file(&dir.join(path.trim()), including, equations)?;
+            }
+            Some(_) => bail!("Expected a file to include, in {line:?}"),
+            None => equations.push(line.to_string()),
+        }
+    }
+    Ok(())
+}
+
+/// Solves the system in `input`, printing the solution, and returns the exit code for it.
+pub fn solve(input: Input, method: Method) -> Result<ExitCode> {
+    let sources = input.lines()?;
+    if sources.is_empty() {
+        eprintln!("No equations to solve.");
+        return Ok(ExitCode::from(INVALID));
+    }
+
+    // Report every equation that fails to parse, not just the first.
+    let exprs = sources
+        .iter()
+        .map(|source| parse_equation(source).ok())
+        .collect::<Vec<_>>();
+    let Some(exprs) = exprs.into_iter().collect::<Option<Vec<_>>>() else {
+        return Ok(ExitCode::from(INVALID));
+    };
+    let spans = exprs
+        .iter()
+        .map(|expr| expr.span.clone())
+        .collect::<Vec<_>>();
+
+    // The matrix method also reports how well conditioned the system is, on stderr so the
+    // solution can be read from stdout alone.
+    let solution = match method {
+        Method::Matrix => solve::solve_with_diagnostics(exprs, 0).map(|(solution, diagnostics)| {
+            if let Some(diagnostics) = diagnostics {
+                eprintln!("{diagnostics}");
+                warn_ill_conditioned(&solution, &diagnostics, false);
+            }
+            solution
+        }),
+        method => solve::solve_with_method(exprs, method),
+    };
+    match solution {
+        Ok(solution) => {
+            println!("{solution}");
+            if let SolutionSet::Inconsistent { equations } = &solution {
+                report_contradiction(equations, &spans, &sources)?;
+                return Ok(ExitCode::from(INCONSISTENT));
+            }
+            Ok(ExitCode::from(SOLVED))
+        }
+        Err(solve::Error::ExprToStandardFormConversionFail(i, err)) => {
+            err.report().eprint(Source::from(&sources[i - 1]))?;
+            eprintln!("Failed to convert equation {i} to standard form");
+            Ok(ExitCode::from(INVALID))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solve_equations(equations: &[&str], method: Method) -> Result<ExitCode> {
+        let equations = equations.iter().map(ToString::to_string).collect();
+        solve(Input::Equations(equations), method)
+    }
+
+    #[test]
+    fn exit_codes() {
+        let code = |equations: &[&str]| solve_equations(equations, Method::Matrix).unwrap();
+        assert_eq!(code(&["x + y = 3", "x - y = 1"]), ExitCode::from(SOLVED));
+        assert_eq!(code(&["x + y = 3"]), ExitCode::from(SOLVED));
+        assert_eq!(code(&["x = 1", "x = 2"]), ExitCode::from(INCONSISTENT));
+        assert_eq!(code(&["x + = 1"]), ExitCode::from(INVALID));
+        assert_eq!(code(&["xy = 1"]), ExitCode::from(INVALID));
+        assert_eq!(code(&[]), ExitCode::from(INVALID));
+
+        // Any other error is returned, for `main` to exit with 1.
+        assert!(solve_equations(&["x + y = 1"], Method::Jacobi).is_err());
+        assert!(solve(
+            Input::File(PathBuf::from("/nonexistent/system.txt")),
+            Method::Matrix
+        )
+        .is_err());
+    }
+
+    /// A fresh directory for a test's files.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sles-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn relative_includes() {
+        let dir = temp_dir("relative_includes");
+        std::fs::create_dir(dir.join("parts")).unwrap();
+        std::fs::write(
+            dir.join("system.txt"),
+            "x + y = 3\n\n:include parts/rest.txt\n",
+        )
+        .unwrap();
+        // Relative to parts/rest.txt, not to system.txt or the working directory.
+        std::fs::write(dir.join("parts/rest.txt"), ":include last.txt\nx - y = 1\n").unwrap();
+        std::fs::write(dir.join("parts/last.txt"), "z = 2\n").unwrap();
+
+        assert_eq!(
+            read_equations(&dir.join("system.txt")).unwrap(),
+            ["x + y = 3", "z = 2", "x - y = 1"]
+        );
+        let mut equations = Vec::new();
+        read_lines(
+            ":include rest.txt",
+            &dir.join("parts"),
+            &mut Vec::new(),
+            &mut equations,
+        )
+        .unwrap();
+        assert_eq!(equations, ["z = 2", "x - y = 1"]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn recursive_includes() {
+        let dir = temp_dir("recursive_includes");
+        std::fs::write(dir.join("a.txt"), "x = 1\n:include b.txt\n").unwrap();
+        std::fs::write(dir.join("b.txt"), ":include ./a.txt\n").unwrap();
+        std::fs::write(dir.join("self.txt"), ":include self.txt\n").unwrap();
+        // Including the same file twice is fine, as long as it does not include itself.
+        std::fs::write(dir.join("twice.txt"), ":include c.txt\n:include c.txt\n").unwrap();
+        std::fs::write(dir.join("c.txt"), "y = 2\n").unwrap();
+
+        for file in ["a.txt", "self.txt"] {
+            let err = read_equations(&dir.join(file)).unwrap_err();
+            assert!(
+                err.chain()
+                    .any(|cause| cause.to_string().ends_with("includes itself")),
+                "{err:?}"
+            );
+        }
+        assert_eq!(
+            read_equations(&dir.join("twice.txt")).unwrap(),
+            ["y = 2", "y = 2"]
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,41 +1,448 @@
-use std::collections::BTreeMap;
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+};
 
 use thiserror::Error;
 
-use crate::{expr::Expr, matrix::MatrixForm, standardform::StandardForm};
+use crate::{
+    expr::Expr,
+    gaussian::{self, Trace},
+    iterative::{History, Options, Scheme},
+    matrix::{Determinants, Diagnostics, Factorised, MatrixForm},
+    scalar::{Rational, Scalar},
+    sparse::{self, SparseForm},
+    standardform::StandardForm,
+    symbolic,
+};
 
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("Failed to convert the equation from an expression to standard form.")]
-    ExprToStandardFormConversionFail(#[source] crate::standardform::Error),
+    #[error("Failed to convert equation {0} from an expression to standard form.")]
+    ExprToStandardFormConversionFail(usize, #[source] crate::standardform::Error),
     #[error("Failed to convert the equation from standard form to matrix form.")]
     StandardFormToMatrixFormConversionFail(#[source] crate::matrix::Error),
+    #[error("Failed to solve the equations by gaussian elimination.")]
+    GaussianEliminationFail(#[source] crate::gaussian::Error),
+    #[error("Failed to solve the equations in matrix form.")]
+    MatrixSolveFail(#[source] crate::matrix::Error),
+    #[error("Failed to solve the equations symbolically.")]
+    SymbolicSolveFail(#[source] crate::symbolic::Error),
+    #[error("The {0:?} method does not show its working.")]
+    NoWorking(Method),
+    #[error("Failed to solve the equations iteratively.")]
+    IterativeSolveFail(#[source] crate::iterative::Error),
+    #[error("The {0:?} method is not iterative.")]
+    NotIterative(Method),
 }
 pub type Result<T> = core::result::Result<T, Error>;
 
-pub type Solution = BTreeMap<char, f64>;
+pub type Solution<T = f64> = BTreeMap<String, T>;
+
+/// Every solution to a system of equations.
+#[derive(Debug, Clone)]
+pub enum SolutionSet<T = f64> {
+    /// The system has exactly one solution.
+    Unique(Solution<T>),
+    /// The system has infinitely many solutions, one for each choice of the free pronumerals.
+    /// `unused` are the free pronumerals that cancel out of every equation they appear in.
+    Infinite {
+        free: Vec<String>,
+        unused: Vec<String>,
+        pivots: BTreeMap<String, Parametric<T>>,
+    },
+    /// The system has no solution. `equations` (1-based) are the equations that contradict each other.
+    Inconsistent { equations: Vec<usize> },
+    /// A best fit rather than an exact solution: the least-squares solution of an overdetermined
+    /// system or the minimum-norm solution of an underdetermined one.
+    /// `residuals` holds `lhs - rhs` for each equation.
+    Approximate {
+        solution: Solution<T>,
+        residuals: Vec<T>,
+    },
+}
+
+/// A pronumeral's value in terms of the free pronumerals, `constant + Σ coeff * free`.
+#[derive(Debug, Clone)]
+pub struct Parametric<T = f64> {
+    pub constant: T,
+    pub terms: BTreeMap<String, T>,
+}
+
+impl<T: Scalar> SolutionSet<T> {
+    /// The value of each pronumeral, if there is a single (unique or best fit) solution.
+    pub fn values(&self) -> Option<Solution> {
+        match self {
+            SolutionSet::Unique(solution) | SolutionSet::Approximate { solution, .. } => Some(
+                solution
+                    .iter()
+                    .map(|(var, value)| (var.clone(), value.approximate()))
+                    .collect(),
+            ),
+            SolutionSet::Infinite { .. } | SolutionSet::Inconsistent { .. } => None,
+        }
+    }
+}
+
+impl<T: Scalar> Display for SolutionSet<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolutionSet::Unique(solution) => {
+                let lines = solution
+                    .iter()
+                    .map(|(var, val)| format!("{var} = {val}"))
+                    .collect::<Vec<_>>();
+                write!(f, "{}", lines.join("\n"))
+            }
+            SolutionSet::Infinite {
+                free,
+                unused,
+                pivots,
+            } => {
+                write!(
+                    f,
+                    "There are infinitely many solutions, where {} can take any value.",
+                    free.join(", ")
+                )?;
+                if !unused.is_empty() {
+                    let verb = if unused.len() == 1 {
+                        "cancels"
+                    } else {
+                        "cancel"
+                    };
+                    write!(
+                        f,
+                        "\n{} {verb} out of every equation, so nothing determines {}.",
+                        unused.join(", "),
+                        if unused.len() == 1 { "it" } else { "them" }
+                    )?;
+                }
+                for (var, parametric) in pivots {
+                    write!(f, "\n{var} = {parametric}")?;
+                }
+                Ok(())
+            }
+            SolutionSet::Inconsistent { equations } if equations.len() == 1 => write!(
+                f,
+                "There is no solution, equation {} is a contradiction.",
+                equations[0]
+            ),
+            SolutionSet::Inconsistent { equations } => write!(
+                f,
+                "There is no solution, equations {} contradict each other.",
+                equations
+                    .iter()
+                    .map(std::string::ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            SolutionSet::Approximate {
+                solution,
+                residuals,
+            } => {
+                for (var, val) in solution {
+                    writeln!(f, "{var} = {val}")?;
+                }
+                write!(f, "Residuals:")?;
+                for (i, residual) in residuals.iter().enumerate() {
+                    write!(f, "\n  ({}) {}", i + 1, residual.abs())?;
+                }
+                let norm = residuals
+                    .iter()
+                    .map(|r| r.approximate().powi(2))
+                    .sum::<f64>()
+                    .sqrt();
+                write!(f, "\nResidual norm: {norm}")
+            }
+        }
+    }
+}
+
+impl<T: Scalar> Display for Parametric<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut written = false;
+        if !self.constant.is_zero() || self.terms.is_empty() {
+            write!(f, "{}", self.constant)?;
+            written = true;
+        }
+        for (var, coeff) in &self.terms {
+            let sign = if coeff.is_negative() { "-" } else { "+" };
+            if written {
+                write!(f, " {sign} ")?;
+            } else if coeff.is_negative() {
+                write!(f, "-")?;
+            }
+            if !coeff.abs().is_one() {
+                write!(f, "{}", coeff.abs())?;
+            }
+            write!(f, "{var}")?;
+            written = true;
+        }
+        Ok(())
+    }
+}
 
 #[derive(Debug, Clone, clap::ValueEnum)]
 pub enum Method {
     Matrix,
     General,
+    LeastSquares,
+    MinimumNorm,
+    /// Make a pronumeral the subject of one equation and substitute it into the others.
+    Substitution,
+    /// Add multiples of equations together to cancel pronumerals out.
+    Elimination,
+    /// Divide determinants, by Cramer's rule.
+    Cramer,
+    /// Iterate, updating every pronumeral from the previous estimate.
+    Jacobi,
+    /// Iterate, updating each pronumeral from the newest estimate.
+    GaussSeidel,
+    /// Iterate like Gauss-Seidel with successive over-relaxation.
+    Sor,
+}
+
+fn to_standard_form<T: Scalar>(exprs: Vec<Expr>) -> Result<Vec<StandardForm<T>>> {
+    exprs
+        .into_iter()
+        .enumerate()
+        .map(|(i, expr)| {
+            StandardForm::try_from(expr)
+                .map_err(|err| Error::ExprToStandardFormConversionFail(i + 1, err))
+        })
+        .collect()
 }
 
-pub fn solve_with_method(exprs: Vec<Expr>, method: Method) -> Result<Solution> {
+pub fn solve_with_method(exprs: Vec<Expr>, method: Method) -> Result<SolutionSet> {
+    let standard_eqs = to_standard_form(exprs)?;
+
     match method {
         Method::Matrix => {
-            let standard_eqs = exprs
-                .into_iter()
-                .map(StandardForm::try_from)
-                .map(|r| r.map_err(Error::ExprToStandardFormConversionFail))
-                .collect::<Result<Vec<_>>>()?;
-
+            if let Some(solution) = solve_sparse(&standard_eqs) {
+                return Ok(solution);
+            }
             let matrix = MatrixForm::try_from(standard_eqs)
                 .map_err(Error::StandardFormToMatrixFormConversionFail)?;
             Ok(matrix.solve())
         }
-        Method::General => {
-            todo!()
+        Method::General => gaussian::solve(&standard_eqs).map_err(Error::GaussianEliminationFail),
+        Method::LeastSquares => MatrixForm::try_from(standard_eqs)
+            .map_err(Error::StandardFormToMatrixFormConversionFail)?
+            .least_squares()
+            .map_err(Error::MatrixSolveFail),
+        Method::MinimumNorm => MatrixForm::try_from(standard_eqs)
+            .map_err(Error::StandardFormToMatrixFormConversionFail)?
+            .minimum_norm()
+            .map_err(Error::MatrixSolveFail),
+        Method::Cramer => MatrixForm::try_from(standard_eqs)
+            .map_err(Error::StandardFormToMatrixFormConversionFail)?
+            .cramer()
+            .map(|(solution, _)| solution)
+            .map_err(Error::MatrixSolveFail),
+        Method::Jacobi | Method::GaussSeidel | Method::Sor => {
+            let matrix = MatrixForm::try_from(standard_eqs)
+                .map_err(Error::StandardFormToMatrixFormConversionFail)?;
+            iterate(&matrix, method, Options::default()).map(|(solution, _)| solution)
+        }
+        Method::Substitution => symbolic::substitution(&standard_eqs)
+            .map(|(solution, _)| solution)
+            .map_err(Error::SymbolicSolveFail),
+        Method::Elimination => symbolic::elimination(&standard_eqs)
+            .map(|(solution, _)| solution)
+            .map_err(Error::SymbolicSolveFail),
+    }
+}
+
+/// Solves large sparse systems without building the dense matrix. The sparse solvers only find
+/// unique solutions, so anything else is left to the dense path to classify.
+fn solve_sparse(standard_eqs: &[StandardForm]) -> Option<SolutionSet> {
+    if !sparse::is_worthwhile(standard_eqs) {
+        return None;
+    }
+    SparseForm::try_from(standard_eqs)
+        .and_then(SparseForm::solve)
+        .ok()
+}
+
+/// Solves the equations like [`Method::Matrix`], along with how accurate the solution is likely
+/// to be after `refinements` steps of iterative refinement.
+///
+/// Large sparse systems are solved without diagnostics, as the condition number needs the
+/// dense matrix.
+pub fn solve_with_diagnostics(
+    exprs: Vec<Expr>,
+    refinements: usize,
+) -> Result<(SolutionSet, Option<Diagnostics>)> {
+    let standard_eqs = to_standard_form(exprs)?;
+    if let Some(solution) = solve_sparse(&standard_eqs) {
+        return Ok((solution, None));
+    }
+
+    let matrix = MatrixForm::try_from(standard_eqs)
+        .map_err(Error::StandardFormToMatrixFormConversionFail)?;
+    let (solution, diagnostics) = matrix.solve_with_diagnostics(refinements);
+    Ok((solution, Some(diagnostics)))
+}
+
+/// Solves the equations by substitution or elimination, returning each line of working.
+pub fn solve_with_working<T: Scalar>(
+    exprs: Vec<Expr>,
+    method: Method,
+) -> Result<(SolutionSet<T>, Vec<String>)> {
+    let standard_eqs = to_standard_form(exprs)?;
+
+    match method {
+        Method::Substitution => {
+            symbolic::substitution(&standard_eqs).map_err(Error::SymbolicSolveFail)
+        }
+        Method::Elimination => {
+            symbolic::elimination(&standard_eqs).map_err(Error::SymbolicSolveFail)
+        }
+        method => Err(Error::NoWorking(method)),
+    }
+}
+
+/// Solves the equations by gaussian elimination in exact rational arithmetic.
+pub fn solve_exact(exprs: Vec<Expr>) -> Result<SolutionSet<Rational>> {
+    let standard_eqs = to_standard_form(exprs)?;
+    gaussian::solve(&standard_eqs).map_err(Error::GaussianEliminationFail)
+}
+
+/// Solves the equations by elimination on their augmented matrix, recording each row operation.
+pub fn solve_with_steps(exprs: Vec<Expr>) -> Result<(SolutionSet, Trace<f64>)> {
+    let matrix = MatrixForm::try_from(to_standard_form(exprs)?)
+        .map_err(Error::StandardFormToMatrixFormConversionFail)?;
+    Ok(matrix.solve_with_steps())
+}
+
+/// Solves the equations like [`solve_exact`], recording each row operation.
+pub fn solve_exact_with_steps(
+    exprs: Vec<Expr>,
+) -> Result<(SolutionSet<Rational>, Trace<Rational>)> {
+    let standard_eqs = to_standard_form(exprs)?;
+    gaussian::solve_with_steps(&standard_eqs).map_err(Error::GaussianEliminationFail)
+}
+
+/// The determinants of the equations' coefficient matrix, as used by Cramer's rule.
+pub fn determinants(exprs: Vec<Expr>) -> Result<Determinants> {
+    MatrixForm::try_from(to_standard_form(exprs)?)
+        .map_err(Error::StandardFormToMatrixFormConversionFail)?
+        .determinants()
+        .map_err(Error::MatrixSolveFail)
+}
+
+/// Solves the equations by Cramer's rule, returning the determinants alongside the solution.
+pub fn solve_with_determinants(exprs: Vec<Expr>) -> Result<(SolutionSet, Determinants)> {
+    MatrixForm::try_from(to_standard_form(exprs)?)
+        .map_err(Error::StandardFormToMatrixFormConversionFail)?
+        .cramer()
+        .map_err(Error::MatrixSolveFail)
+}
+
+/// Solves the equations by the Jacobi, Gauss-Seidel or SOR method, returning how much each
+/// iteration changed the solution.
+pub fn solve_iteratively(
+    exprs: Vec<Expr>,
+    method: Method,
+    options: Options,
+) -> Result<(SolutionSet, History)> {
+    let matrix = MatrixForm::try_from(to_standard_form(exprs)?)
+        .map_err(Error::StandardFormToMatrixFormConversionFail)?;
+    iterate(&matrix, method, options)
+}
+
+fn iterate(
+    matrix: &MatrixForm,
+    method: Method,
+    options: Options,
+) -> Result<(SolutionSet, History)> {
+    let scheme = match method {
+        Method::Jacobi => Scheme::Jacobi,
+        Method::GaussSeidel => Scheme::GaussSeidel,
+        Method::Sor => Scheme::Sor,
+        method => return Err(Error::NotIterative(method)),
+    };
+    matrix
+        .iterate(scheme, options)
+        .map_err(Error::IterativeSolveFail)
+}
+
+/// Factorises the equations' coefficients, so they can be solved for many sets of constants.
+pub fn factorise(exprs: Vec<Expr>) -> Result<Factorised> {
+    MatrixForm::try_from(to_standard_form(exprs)?)
+        .map_err(Error::StandardFormToMatrixFormConversionFail)?
+        .factorise()
+        .map_err(Error::MatrixSolveFail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::parse_equations;
+
+    fn solve(sources: &[&str], method: Method) -> SolutionSet {
+        solve_with_method(parse_equations(sources), method).unwrap()
+    }
+
+    #[test]
+    fn classification() {
+        assert!(matches!(
+            solve(&["x + y = 3", "x - y = 1"], Method::General),
+            SolutionSet::Unique(solution) if solution["x"] == 2.0 && solution["y"] == 1.0
+        ));
+        assert!(matches!(
+            solve(&["x + y = 3", "2x + 2y = 6"], Method::General),
+            SolutionSet::Infinite { free, .. } if free == ["y"]
+        ));
+        assert!(matches!(
+            solve(&["x + y = 3", "x + y = 4"], Method::General),
+            SolutionSet::Inconsistent { equations } if equations == [1, 2]
+        ));
+    }
+
+    #[test]
+    fn general_matches_matrix() {
+        let systems: &[&[&str]] = &[
+            &["2x + 3y = 7", "x - y = 1"],
+            &["z + y = 2", "x - 2z = 0.5", "3x + y - z = 4"],
+            &["x + y + z = 1", "2x + 2y + 2z = 2"],
+            &["x + y = 1", "x + y = 2", "x = 0"],
+        ];
+        for sources in systems {
+            match (
+                solve(sources, Method::General),
+                solve(sources, Method::Matrix),
+            ) {
+                (SolutionSet::Unique(general), SolutionSet::Unique(matrix)) => {
+                    assert_eq!(
+                        general.keys().collect::<Vec<_>>(),
+                        matrix.keys().collect::<Vec<_>>()
+                    );
+                    for (var, value) in general {
+                        assert!((value - matrix[&var]).abs() < 1e-9, "{sources:?}: {var}");
+                    }
+                }
+                (
+                    SolutionSet::Infinite { free: general, .. },
+                    SolutionSet::Infinite { free: matrix, .. },
+                ) => assert_eq!(general, matrix, "{sources:?}"),
+                (SolutionSet::Inconsistent { .. }, SolutionSet::Inconsistent { .. }) => {}
+                (general, matrix) => {
+                    panic!("{sources:?}: general gave {general:?}, matrix gave {matrix:?}")
+                }
+            }
         }
     }
+
+    #[test]
+    fn exact_thirds() {
+        let third = Rational::new(1.into(), 3.into());
+        let SolutionSet::Unique(solution) =
+            solve_exact(parse_equations(&["x/3 + y = 1", "3y = 1"])).unwrap()
+        else {
+            panic!("expected a unique solution");
+        };
+        assert_eq!(solution["x"], Rational::from_integer(2.into()));
+        assert_eq!(solution["y"], third);
+    }
 }
@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+
+use nalgebra::DVector;
+use nalgebra_sparse::{CooMatrix, CsrMatrix};
+use thiserror::Error;
+
+use crate::{gaussian::EPSILON, solve::SolutionSet, standardform::StandardForm};
+
+/// Systems with fewer pronumerals than this are small enough to solve densely.
+pub const MIN_VARIABLES: usize = 100;
+/// Systems with a larger fraction of nonzero coefficients than this are solved densely.
+pub const MAX_DENSITY: f64 = 0.1;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("There are no equations to solve!")]
+    NoEquations,
+    #[error("The sparse solvers need as many equations as pronumerals")]
+    NotSquare,
+    #[error("The coefficients are singular, so there is no unique solution")]
+    Singular,
+}
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// A system stored as a compressed sparse row matrix, holding only the nonzero coefficients.
+#[derive(Debug)]
+pub struct SparseForm {
+    coefficients: CsrMatrix<f64>,
+    variables: Vec<String>,
+    constants: DVector<f64>,
+}
+
+/// Whether the system is large and sparse enough that the sparse solvers beat a dense matrix.
+pub fn is_worthwhile(equations: &[StandardForm]) -> bool {
+    let variables = variables(equations);
+    let nonzeros = equations
+        .iter()
+        .map(|equation| equation.terms.len())
+        .sum::<usize>();
+    let size = equations.len() * variables.len();
+
+    variables.len() >= MIN_VARIABLES
+        && equations.len() == variables.len()
+        && (nonzeros as f64) <= MAX_DENSITY * size as f64
+}
+
+/// Numbers every pronumeral used by any equation, in order.
+fn variables(equations: &[StandardForm]) -> BTreeMap<&str, usize> {
+    let mut variables = BTreeMap::new();
+    for equation in equations {
+        for var in equation.pronumerals() {
+            variables.insert(var.as_str(), 0);
+        }
+    }
+    for (i, index) in variables.values_mut().enumerate() {
+        *index = i;
+    }
+    variables
+}
+
+impl TryFrom<&[StandardForm]> for SparseForm {
+    type Error = Error;
+
+    fn try_from(equations: &[StandardForm]) -> Result<Self> {
+        if equations.is_empty() {
+            return Err(Error::NoEquations);
+        }
+
+        let columns = variables(equations);
+        let mut coo = CooMatrix::new(equations.len(), columns.len());
+        for (row, equation) in equations.iter().enumerate() {
+            for (var, coeff) in &equation.terms {
+                if *coeff != 0.0 {
+                    coo.push(row, columns[var.as_str()], *coeff);
+                }
+            }
+        }
+
+        Ok(SparseForm {
+            coefficients: CsrMatrix::from(&coo),
+            variables: columns.keys().map(|var| var.to_string()).collect(),
+            constants: DVector::from_iterator(
+                equations.len(),
+                equations.iter().map(|equation| equation.constant),
+            ),
+        })
+    }
+}
+
+impl SparseForm {
+    /// Solves a square system by gaussian elimination on its sparse rows, with partial pivoting.
+    /// Each row only stores its nonzero coefficients, so banded systems stay cheap.
+    ///
+    /// This only finds unique solutions, a singular system fails with [`Error::Singular`].
+    pub fn solve(self) -> Result<SolutionSet> {
+        let n = self.coefficients.nrows();
+        if n != self.coefficients.ncols() {
+            return Err(Error::NotSquare);
+        }
+
+        let mut rows = self
+            .coefficients
+            .row_iter()
+            .map(|row| {
+                row.col_indices()
+                    .iter()
+                    .copied()
+                    .zip(row.values().iter().copied())
+                    .collect::<BTreeMap<usize, f64>>()
+            })
+            .collect::<Vec<_>>();
+        let mut constants = self.constants.iter().copied().collect::<Vec<_>>();
+        // The largest coefficient in each column, pivots are compared with these so the scale of
+        // the system does not matter.
+        let mut scales = vec![0.0_f64; n];
+        for row in &rows {
+            for (&col, value) in row {
+                scales[col] = scales[col].max(value.abs());
+            }
+        }
+
+        for col in 0..n {
+            let pivot = (col..n)
+                .filter_map(|row| rows[row].get(&col).map(|value| (row, value.abs())))
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .filter(|(_, value)| *value > EPSILON * scales[col])
+                .map(|(row, _)| row)
+                .ok_or(Error::Singular)?;
+            rows.swap(col, pivot);
+            constants.swap(col, pivot);
+
+            let (done, rest) = rows.split_at_mut(col + 1);
+            let pivot_row = &done[col];
+            let pivot_value = pivot_row[&col];
+            for (offset, row) in rest.iter_mut().enumerate() {
+                let Some(value) = row.remove(&col) else {
+                    continue;
+                };
+                let factor = value / pivot_value;
+                for (&j, &coeff) in pivot_row.range(col + 1..) {
+                    let entry = row.entry(j).or_insert(0.0);
+                    *entry -= factor * coeff;
+                    if *entry == 0.0 {
+                        row.remove(&j);
+                    }
+                }
+                constants[col + 1 + offset] -= factor * constants[col];
+            }
+        }
+
+        let mut x = vec![0.0; n];
+        for col in (0..n).rev() {
+            let sum = rows[col]
+                .range(col + 1..)
+                .map(|(&j, coeff)| coeff * x[j])
+                .sum::<f64>();
+            x[col] = (constants[col] - sum) / rows[col][&col];
+        }
+
+        let solution = self.variables.into_iter().zip(x).collect();
+        Ok(SolutionSet::Unique(solution))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{gaussian, standardform::parse_standard_forms};
+
+    /// A pentadiagonal system of `n` equations, with every coefficient multiplied by `scale`.
+    fn banded(n: usize, scale: f64) -> Vec<String> {
+        (0..n)
+            .map(|i| {
+                let mut source = format!("{}x{i}", 6.0 * scale);
+                for j in [i.wrapping_sub(2), i.wrapping_sub(1), i + 1, i + 2] {
+                    if j < n {
+                        source += &format!(" - {}x{j}", scale * (1.0 + (i + j) as f64 % 3.0) / 3.0);
+                    }
+                }
+                source + &format!(" = {}", scale * (i % 5) as f64)
+            })
+            .collect()
+    }
+
+    fn assert_matches_dense(sources: &[String]) {
+        let sources = sources.iter().map(String::as_str).collect::<Vec<_>>();
+        let equations = parse_standard_forms(&sources);
+        assert!(is_worthwhile(&equations));
+
+        let SolutionSet::Unique(expected) = gaussian::solve(&equations).unwrap() else {
+            panic!("expected a unique solution");
+        };
+        let SolutionSet::Unique(solution) = SparseForm::try_from(equations.as_slice())
+            .unwrap()
+            .solve()
+            .unwrap()
+        else {
+            panic!("expected a unique solution");
+        };
+        assert_eq!(solution.len(), expected.len());
+        for (var, value) in solution {
+            assert!((value - expected[&var]).abs() < 1e-9, "{var} = {value}");
+        }
+    }
+
+    #[test]
+    fn banded_system() {
+        assert_matches_dense(&banded(150, 1.0));
+    }
+
+    #[test]
+    fn small_scale() {
+        assert_matches_dense(&banded(150, 1e-12));
+    }
+
+    #[test]
+    fn singular() {
+        let mut sources = banded(150, 1.0);
+        sources[1] = sources[0].clone();
+        let sources = sources.iter().map(String::as_str).collect::<Vec<_>>();
+        let equations = parse_standard_forms(&sources);
+        assert!(matches!(
+            SparseForm::try_from(equations.as_slice()).unwrap().solve(),
+            Err(Error::Singular)
+        ));
+    }
+}
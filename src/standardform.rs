@@ -1,80 +1,197 @@
-use std::collections::BTreeMap;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{Display, Formatter},
+};
 
+use ariadne::{Label, Report, ReportKind};
 use thiserror::Error;
 
-use crate::{expr::Expr, termlist::TermList};
+use crate::{
+    expr::{Expr, ExprKind, Span},
+    scalar::Scalar,
+    termlist::TermList,
+};
 
+/// A linear equation `a₁x₁ + a₂x₂ + ... = c`, with coefficients of type `T`.
 #[derive(Debug)]
-pub struct StandardForm {
-    pub terms: BTreeMap<char, f64>,
-    pub constant: f64,
+pub struct StandardForm<T = f64> {
+    pub terms: BTreeMap<String, T>,
+    pub constant: T,
+    /// The source of the whole equation.
+    pub span: Span,
+    /// The source of the (first) term each pronumeral came from.
+    pub spans: BTreeMap<String, Span>,
+    /// The pronumerals that appear in the source but cancel out, like `x` in `x - x + y = 1`.
+    pub cancelled: BTreeSet<String>,
+}
+
+impl<T: Scalar> Display for StandardForm<T> {
+    /// Writes the equation as it would be typed, like `2x - y = 7`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, (var, coeff)) in self.terms.iter().enumerate() {
+            match (i, coeff.is_negative()) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => {}
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+            let coeff = coeff.abs();
+            if !coeff.is_one() {
+                // A fraction is bracketed, so `(1/2)x` is not read as `1/(2x)`.
+                let coeff = coeff.to_string();
+                if coeff.contains('/') {
+                    write!(f, "({coeff})")?;
+                } else {
+                    write!(f, "{coeff}")?;
+                }
+            }
+            write!(f, "{var}")?;
+        }
+        if self.terms.is_empty() {
+            write!(f, "0")?;
+        }
+        write!(f, " = {}", self.constant)
+    }
+}
+
+impl<T> StandardForm<T> {
+    /// Every pronumeral in the equation, including those that cancelled out, in order.
+    pub fn pronumerals(&self) -> impl Iterator<Item = &String> {
+        let mut pronumerals = self.terms.keys().chain(&self.cancelled).collect::<Vec<_>>();
+        pronumerals.sort();
+        pronumerals.into_iter()
+    }
 }
 
 #[derive(Error, Debug)]
 pub enum Error {
-    #[error("The expr is not in standard form.")]
-    NotStandardForm,
+    #[error("The term {0} is a product of pronumerals, so the equation is not linear")]
+    NonlinearTerm(String, Span),
+    #[error("A term involves π, e or a fractional power, so it has no exact value")]
+    InexactTerm(Span),
+    #[error("A coefficient is too large or too small to be represented as a decimal number")]
+    OutOfRange(Span),
     #[error("Expected equation")]
     NotEquation,
+    #[error("Failed to expand the equation into a list of terms.")]
+    TermListConversionFail(#[source] crate::termlist::Error),
 }
 
-impl TryFrom<Expr> for StandardForm {
+impl Error {
+    /// Renders the error against the source of the equation that caused it.
+    pub fn report(&self) -> Report<'static> {
+        match self {
+            Error::NonlinearTerm(_, span) => Report::build(ReportKind::Error, (), span.start)
+                .with_message(self)
+                .with_label(Label::new(span.clone()).with_message("this term is not linear"))
+                .finish(),
+            Error::InexactTerm(span) => Report::build(ReportKind::Error, (), span.start)
+                .with_message(self)
+                .with_label(Label::new(span.clone()).with_message("this term is not exact"))
+                .finish(),
+            Error::OutOfRange(span) => Report::build(ReportKind::Error, (), span.start)
+                .with_message(self)
+                .with_label(
+                    Label::new(span.clone()).with_message("this coefficient is out of range"),
+                )
+                .finish(),
+            Error::NotEquation => Report::build(ReportKind::Error, (), 0)
+                .with_message(self)
+                .finish(),
+            Error::TermListConversionFail(err) => err.report(),
+        }
+    }
+}
+
+impl<T: Scalar> TryFrom<Expr> for StandardForm<T> {
     type Error = Error;
 
     fn try_from(value: Expr) -> Result<Self, Self::Error> {
-        let (lhs, rhs) = if let Expr::Equation(lhs, rhs) = value {
+        let span = value.span.clone();
+        let mut cancelled = value.pronumerals();
+        let (lhs, rhs) = if let ExprKind::Equation(lhs, rhs) = value.kind {
             (lhs, rhs)
         } else {
             return Err(Error::NotEquation);
         };
 
-        let lhs = TermList::from_expr(*lhs);
-
-        let constant = {
-            let rhs = TermList::from_expr(*rhs);
-            if rhs.terms.len() != 1 {
-                dbg!(&rhs);
-                return Err(Error::NotStandardForm);
-            }
+        let lhs = TermList::from_expr(*lhs).map_err(Error::TermListConversionFail)?;
+        let rhs = TermList::from_expr(*rhs).map_err(Error::TermListConversionFail)?;
 
-            let rhs = rhs
-                .terms
-                .first()
-                .expect("Already checked for empty termlist");
-            if !rhs.pronumerals.is_empty() {
-                dbg!();
-                return Err(Error::NotStandardForm);
-            }
+        // Move every term to the left, then move the constant terms back to the right.
+        let terms = lhs.subtract(rhs).canonicalise();
 
-            rhs.get_approximate_coefficient()
-        };
+        let mut variables = BTreeMap::<String, T>::new();
+        let mut spans = BTreeMap::new();
+        let mut constant = T::zero();
 
-        let mut variables = BTreeMap::<char, f64>::new();
+        for term in terms.terms {
+            let pronumeral = match term.pronumerals.as_slice() {
+                [] => None,
+                [pronumeral] => Some(pronumeral.clone()),
+                pronumerals => {
+                    return Err(Error::NonlinearTerm(pronumerals.join("*"), term.span));
+                }
+            };
+            let coeff = T::from_term(&term)?;
 
-        for term in lhs.terms {
-            if term.pronumerals.len() != 1 {
-                dbg!();
-                return Err(Error::NotStandardForm);
-            }
-            let pronumeral = term
-                .pronumerals
-                .first()
-                .expect("Already checked for empty termlist");
-            if variables.contains_key(pronumeral) {
-                dbg!();
-                return Err(Error::NotStandardForm);
+            match pronumeral {
+                None => constant = constant - coeff,
+                Some(pronumeral) => {
+                    let entry = variables.entry(pronumeral.clone()).or_insert_with(T::zero);
+                    *entry = entry.clone() + coeff;
+                    spans.entry(pronumeral).or_insert(term.span);
+                }
             }
-            variables.insert(*pronumeral, term.get_approximate_coefficient());
         }
 
-        // sort by pronumeral
-        let mut variables = variables.into_iter().collect::<Vec<_>>();
-        variables.sort_by(|(a, _), (b, _)| a.cmp(b));
-        let variables = BTreeMap::from_iter(variables);
-
+        cancelled.retain(|pronumeral| !variables.contains_key(pronumeral));
         Ok(StandardForm {
             terms: variables,
             constant,
+            span,
+            spans,
+            cancelled,
         })
     }
 }
+
+/// Parses each source as an equation and converts it to standard form, for tests.
+#[cfg(test)]
+pub(crate) fn parse_standard_forms<T: Scalar>(sources: &[&str]) -> Vec<StandardForm<T>> {
+    crate::expr::parse_equations(sources)
+        .into_iter()
+        .map(|expr| StandardForm::try_from(expr).unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::Rational;
+
+    #[test]
+    fn display() {
+        let [equation] = parse_standard_forms::<f64>(&["2x + 3 = y + 10 - 0.5z"])
+            .try_into()
+            .unwrap();
+        assert_eq!(equation.to_string(), "2x - y + 0.5z = 7");
+
+        let [equation] = parse_standard_forms::<Rational>(&["-x + y/2 = 0"])
+            .try_into()
+            .unwrap();
+        assert_eq!(equation.to_string(), "-x + (1/2)y = 0");
+    }
+
+    #[test]
+    fn out_of_range() {
+        for source in ["2^2000 x = 1", "x/2^2000 = 1"] {
+            let expr = crate::expr::parse_equations(&[source]).remove(0);
+            assert!(matches!(
+                StandardForm::<f64>::try_from(expr.clone()),
+                Err(Error::OutOfRange(_))
+            ));
+            assert!(StandardForm::<Rational>::try_from(expr).is_ok());
+        }
+    }
+}
@@ -0,0 +1,558 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{Display, Formatter},
+};
+
+use thiserror::Error;
+
+use crate::{
+    gaussian::largest,
+    scalar::Scalar,
+    solve::{Parametric, SolutionSet},
+    standardform::StandardForm,
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("There are no equations to solve!")]
+    NoEquations,
+}
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// An equation written down while working, numbered like `(3)` so later steps can refer to it.
+#[derive(Debug, Clone)]
+struct Derived<T> {
+    label: usize,
+    terms: BTreeMap<String, T>,
+    constant: T,
+    /// The size of the values `constant` was computed from, to judge whether it is zero.
+    scale: T,
+    /// The original equations (1-based) this was derived from.
+    origins: BTreeSet<usize>,
+}
+
+impl<T: Scalar> Display for Derived<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let lhs = Parametric {
+            constant: T::zero(),
+            terms: self.terms.clone(),
+        };
+        write!(f, "{lhs} = {}", self.constant)
+    }
+}
+
+impl<T: Scalar> Derived<T> {
+    /// The pronumeral to solve for, preferring one with a coefficient of ±1 so no fractions appear.
+    fn choose(&self) -> Option<String> {
+        self.terms
+            .iter()
+            .find(|(_, coeff)| coeff.abs().is_one())
+            .or_else(|| self.terms.iter().next())
+            .map(|(var, _)| var.clone())
+    }
+
+    /// Rearranges the equation to make `var` the subject.
+    fn solve_for(&self, var: &str) -> Parametric<T> {
+        let coeff = self.terms[var].clone();
+        Parametric {
+            constant: self.constant.clone() / coeff.clone(),
+            terms: self
+                .terms
+                .iter()
+                .filter(|(other, _)| *other != var)
+                .map(|(other, c)| (other.clone(), -c.clone() / coeff.clone()))
+                .collect(),
+        }
+    }
+
+    /// Replaces `var` with `value`, returning whether it appeared. `size` is as for [`add_term`].
+    fn substitute(&mut self, var: &str, value: &Parametric<T>, size: usize) -> bool {
+        let Some(coeff) = self.terms.remove(var) else {
+            return false;
+        };
+        let product = coeff.clone() * value.constant.clone();
+        self.scale = largest([&self.scale, &self.constant, &product].into_iter());
+        self.constant = self.constant.clone() - product;
+        for (other, c) in &value.terms {
+            add_term(&mut self.terms, other, coeff.clone() * c.clone(), size);
+        }
+        true
+    }
+
+    /// Whether the equation has no pronumerals left, so it reads `0 = c`.
+    fn is_trivial(&self) -> bool {
+        self.terms.is_empty()
+    }
+}
+
+/// Adds `coeff * var` to `terms`, dropping the term if it cancels out, up to the rounding error
+/// of working with a system of `size` equations or pronumerals.
+fn add_term<T: Scalar>(terms: &mut BTreeMap<String, T>, var: &str, coeff: T, size: usize) {
+    let entry = terms.entry(var.to_string()).or_insert_with(T::zero);
+    let scale = largest([&*entry, &coeff].into_iter());
+    *entry = entry.clone() + coeff;
+    if entry.is_negligible_next_to(&scale, size) {
+        terms.remove(var);
+    }
+}
+
+/// Replaces `var` with `value` in `expr`.
+fn substitute_parametric<T: Scalar>(
+    expr: &mut Parametric<T>,
+    var: &str,
+    value: &Parametric<T>,
+    size: usize,
+) {
+    let Some(coeff) = expr.terms.remove(var) else {
+        return;
+    };
+    expr.constant = expr.constant.clone() + coeff.clone() * value.constant.clone();
+    for (other, c) in &value.terms {
+        add_term(&mut expr.terms, other, coeff.clone() * c.clone(), size);
+    }
+}
+
+/// The working for a system, shared by both methods.
+struct Working<T> {
+    steps: Vec<String>,
+    next_label: usize,
+    variables: BTreeSet<String>,
+    unused: Vec<String>,
+    remaining: Vec<Derived<T>>,
+    /// The number of equations or pronumerals, whichever is larger, which rounding error grows
+    /// with.
+    size: usize,
+}
+
+impl<T: Scalar> Working<T> {
+    fn new(equations: &[StandardForm<T>]) -> Result<Working<T>> {
+        if equations.is_empty() {
+            return Err(Error::NoEquations);
+        }
+
+        let remaining = equations
+            .iter()
+            .enumerate()
+            .map(|(i, equation)| Derived {
+                label: i + 1,
+                terms: equation
+                    .terms
+                    .iter()
+                    .filter(|(_, coeff)| !coeff.is_zero())
+                    .map(|(var, coeff)| (var.clone(), coeff.clone()))
+                    .collect(),
+                constant: equation.constant.clone(),
+                scale: equation.constant.abs(),
+                origins: BTreeSet::from([i + 1]),
+            })
+            .collect::<Vec<Derived<T>>>();
+        let variables = equations
+            .iter()
+            .flat_map(|equation| equation.pronumerals().cloned())
+            .collect::<BTreeSet<_>>();
+        let unused = variables
+            .iter()
+            .filter(|var| !remaining.iter().any(|eq| eq.terms.contains_key(*var)))
+            .cloned()
+            .collect();
+
+        Ok(Working {
+            steps: Vec::new(),
+            next_label: equations.len() + 1,
+            size: equations.len().max(variables.len()),
+            variables,
+            unused,
+            remaining,
+        })
+    }
+
+    fn label(&mut self) -> usize {
+        self.next_label += 1;
+        self.next_label - 1
+    }
+
+    /// Removes equations with no pronumerals left, returning the origins of the first one that
+    /// reads `0 = c` for nonzero `c`.
+    fn discard_trivial(&mut self) -> Option<Vec<usize>> {
+        let (trivial, remaining) = std::mem::take(&mut self.remaining)
+            .into_iter()
+            .partition::<Vec<_>, _>(Derived::is_trivial);
+        self.remaining = remaining;
+
+        for equation in trivial {
+            if equation
+                .constant
+                .is_negligible_next_to(&equation.scale, self.size)
+            {
+                self.steps.push(format!(
+                    "({}) reads 0 = 0, so it adds no information.",
+                    equation.label
+                ));
+            } else {
+                self.steps.push(format!(
+                    "({}) reads 0 = {}, which is impossible.",
+                    equation.label, equation.constant
+                ));
+                return Some(equation.origins.into_iter().collect());
+            }
+        }
+        None
+    }
+
+    /// Back-substitutes the pronumerals made the subject of an equation, last first, and collects
+    /// the solution.
+    fn finish(mut self, subjects: Vec<(String, Parametric<T>)>) -> (SolutionSet<T>, Vec<String>) {
+        let mut pivots = BTreeMap::new();
+        for (var, mut value) in subjects.into_iter().rev() {
+            let known = value
+                .terms
+                .keys()
+                .filter(|other| pivots.contains_key(*other))
+                .cloned()
+                .collect::<Vec<_>>();
+            if !known.is_empty() {
+                for other in &known {
+                    substitute_parametric(&mut value, other, &pivots[other], self.size);
+                }
+                self.steps.push(format!(
+                    "Substitute {} back: {var} = {value}",
+                    known.join(", ")
+                ));
+            }
+            pivots.insert(var, value);
+        }
+
+        let free = self
+            .variables
+            .iter()
+            .filter(|var| !pivots.contains_key(*var))
+            .cloned()
+            .collect::<Vec<_>>();
+        let solution = if free.is_empty() {
+            SolutionSet::Unique(
+                pivots
+                    .into_iter()
+                    .map(|(var, value)| (var, value.constant))
+                    .collect(),
+            )
+        } else {
+            SolutionSet::Infinite {
+                free,
+                unused: self.unused,
+                pivots,
+            }
+        };
+        (solution, self.steps)
+    }
+}
+
+/// Solves the equations by substitution: make a pronumeral the subject of one equation,
+/// substitute it into the rest, and repeat, then substitute back.
+///
+/// Returns the solution along with each derived equation as a line of working.
+pub fn substitution<T: Scalar>(
+    equations: &[StandardForm<T>],
+) -> Result<(SolutionSet<T>, Vec<String>)> {
+    let mut working = Working::new(equations)?;
+    let mut subjects = Vec::new();
+
+    loop {
+        if let Some(equations) = working.discard_trivial() {
+            return Ok((SolutionSet::Inconsistent { equations }, working.steps));
+        }
+        if working.remaining.is_empty() {
+            break;
+        }
+
+        let equation = working.remaining.remove(0);
+        let Some(var) = equation.choose() else {
+            unreachable!("trivial equations were discarded")
+        };
+        let value = equation.solve_for(&var);
+        working
+            .steps
+            .push(format!("From ({}): {var} = {value}", equation.label));
+
+        let mut remaining = std::mem::take(&mut working.remaining);
+        for other in &mut remaining {
+            let label = other.label;
+            if other.substitute(&var, &value, working.size) {
+                other.label = working.label();
+                other.origins.extend(equation.origins.iter().copied());
+                working.steps.push(format!(
+                    "Substitute into ({label}): {other}   ({})",
+                    other.label
+                ));
+            }
+        }
+        working.remaining = remaining;
+        subjects.push((var, value));
+    }
+
+    Ok(working.finish(subjects))
+}
+
+/// Solves the equations by elimination: add multiples of one equation to the rest so a pronumeral
+/// cancels out, and repeat, then solve each set-aside equation by substituting back.
+///
+/// Returns the solution along with each derived equation as a line of working.
+pub fn elimination<T: Scalar>(
+    equations: &[StandardForm<T>],
+) -> Result<(SolutionSet<T>, Vec<String>)> {
+    let mut working = Working::new(equations)?;
+    let mut pivots = Vec::new();
+
+    loop {
+        if let Some(equations) = working.discard_trivial() {
+            return Ok((SolutionSet::Inconsistent { equations }, working.steps));
+        }
+        let Some(var) = working
+            .remaining
+            .iter()
+            .flat_map(|eq| eq.terms.keys())
+            .min()
+            .cloned()
+        else {
+            break;
+        };
+
+        // Eliminate with the simplest coefficient, so the multiples stay small.
+        let index = working
+            .remaining
+            .iter()
+            .enumerate()
+            .filter(|(_, eq)| eq.terms.contains_key(&var))
+            .find(|(_, eq)| eq.terms[&var].abs().is_one())
+            .or_else(|| {
+                working
+                    .remaining
+                    .iter()
+                    .enumerate()
+                    .find(|(_, eq)| eq.terms.contains_key(&var))
+            })
+            .map(|(i, _)| i)
+            .expect("var appears in a remaining equation");
+        let pivot = working.remaining.remove(index);
+        let a = pivot.terms[&var].clone();
+
+        let mut remaining = std::mem::take(&mut working.remaining);
+        for other in &mut remaining {
+            let Some(b) = other.terms.get(&var).cloned() else {
+                continue;
+            };
+            // a × (other) - b × (pivot) cancels var without introducing fractions.
+            let mut terms = BTreeMap::new();
+            for (v, c) in &other.terms {
+                add_term(&mut terms, v, a.clone() * c.clone(), working.size);
+            }
+            for (v, c) in &pivot.terms {
+                add_term(&mut terms, v, -(b.clone() * c.clone()), working.size);
+            }
+            terms.remove(&var);
+            let description = combination(&a, other.label, &b, pivot.label);
+
+            other.terms = terms;
+            other.scale = largest(
+                [
+                    &(a.abs() * other.scale.clone()),
+                    &(b.abs() * pivot.scale.clone()),
+                ]
+                .into_iter(),
+            );
+            other.constant = a.clone() * other.constant.clone() - b * pivot.constant.clone();
+            other.origins.extend(pivot.origins.iter().copied());
+            other.label = working.label();
+            working
+                .steps
+                .push(format!("{description}: {other}   ({})", other.label));
+        }
+        working.remaining = remaining;
+        pivots.push((var, pivot));
+    }
+
+    // Solve the set-aside equations from last to first, so each only needs the values found after it.
+    let mut known: BTreeMap<String, Parametric<T>> = BTreeMap::new();
+    for (var, mut pivot) in pivots.into_iter().rev() {
+        let label = pivot.label;
+        let mut substituted = false;
+        for (other, value) in &known {
+            substituted |= pivot.substitute(other, value, working.size);
+        }
+        let value = pivot.solve_for(&var);
+        let already_solved = pivot.terms.len() == 1 && pivot.terms[&var].is_one();
+        if substituted && already_solved {
+            working
+                .steps
+                .push(format!("Substitute into ({label}): {var} = {value}"));
+        } else if substituted {
+            working.steps.push(format!(
+                "Substitute into ({label}): {pivot}, so {var} = {value}"
+            ));
+        } else {
+            working
+                .steps
+                .push(format!("From ({label}): {var} = {value}"));
+        }
+        known.insert(var, value);
+    }
+
+    Ok(working.finish(known.into_iter().collect()))
+}
+
+/// Describes `a × (target) - b × (source)`, leaving out multiples of one.
+fn combination<T: Scalar>(a: &T, target: usize, b: &T, source: usize) -> String {
+    fn multiple<T: Scalar>(factor: &T, label: usize) -> String {
+        if factor.is_one() {
+            format!("({label})")
+        } else {
+            format!("{factor} × ({label})")
+        }
+    }
+
+    let (sign, b) = if b.is_negative() {
+        ("+", b.abs())
+    } else {
+        ("-", b.clone())
+    };
+    if a.is_negative() {
+        format!(
+            "-{} {sign} {}",
+            multiple(&a.abs(), target),
+            multiple(&b, source)
+        )
+    } else {
+        format!("{} {sign} {}", multiple(a, target), multiple(&b, source))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scalar::Rational, standardform::parse_standard_forms};
+
+    fn working(sources: &[&str]) -> [(SolutionSet<Rational>, Vec<String>); 2] {
+        let equations = parse_standard_forms(sources);
+        [
+            substitution(&equations).unwrap(),
+            elimination(&equations).unwrap(),
+        ]
+    }
+
+    fn whole(n: i32) -> Rational {
+        Rational::from_integer(n.into())
+    }
+
+    #[test]
+    fn unique() {
+        let [(substituted, substitution), (eliminated, elimination)] =
+            working(&["2x + 3y = 7", "3x - 2y = 4"]);
+        for solution in [substituted, eliminated] {
+            assert!(matches!(
+                solution,
+                SolutionSet::Unique(solution) if solution["x"] == whole(2) && solution["y"] == whole(1)
+            ));
+        }
+        assert_eq!(
+            substitution,
+            [
+                "From (1): x = 7/2 - 3/2y",
+                "Substitute into (2): -13/2y = -13/2   (3)",
+                "From (3): y = 1",
+                "Substitute y back: x = 2",
+            ]
+        );
+        assert_eq!(
+            elimination,
+            [
+                "2 × (2) - 3 × (1): -13y = -13   (3)",
+                "From (3): y = 1",
+                "Substitute into (1): 2x = 4, so x = 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn infinite() {
+        let [(substituted, substitution), (eliminated, elimination)] =
+            working(&["x + y = 3", "2x + 2y = 6"]);
+        for solution in [substituted, eliminated] {
+            let SolutionSet::Infinite { free, pivots, .. } = solution else {
+                panic!("expected infinitely many solutions");
+            };
+            assert_eq!(free, ["y"]);
+            assert_eq!(pivots["x"].to_string(), "3 - y");
+        }
+        assert_eq!(
+            substitution,
+            [
+                "From (1): x = 3 - y",
+                "Substitute into (2): 0 = 0   (3)",
+                "(3) reads 0 = 0, so it adds no information.",
+            ]
+        );
+        assert_eq!(
+            elimination,
+            [
+                "(2) - 2 × (1): 0 = 0   (3)",
+                "(3) reads 0 = 0, so it adds no information.",
+                "From (1): x = 3 - y",
+            ]
+        );
+    }
+
+    #[test]
+    fn inconsistent() {
+        let [(substituted, substitution), (eliminated, elimination)] =
+            working(&["x + y = 3", "2x + 2y = 5"]);
+        for solution in [substituted, eliminated] {
+            assert!(matches!(
+                solution,
+                SolutionSet::Inconsistent { equations } if equations == [1, 2]
+            ));
+        }
+        assert_eq!(
+            substitution,
+            [
+                "From (1): x = 3 - y",
+                "Substitute into (2): 0 = -1   (3)",
+                "(3) reads 0 = -1, which is impossible.",
+            ]
+        );
+        assert_eq!(
+            elimination,
+            [
+                "(2) - 2 × (1): 0 = -1   (3)",
+                "(3) reads 0 = -1, which is impossible.",
+            ]
+        );
+    }
+
+    #[test]
+    fn small_coefficients() {
+        let equations = parse_standard_forms::<f64>(&[
+            "0.000000000001x + 0.000000000001y = 0.000000000002",
+            "0.000000000001x - 0.000000000001y = 0",
+        ]);
+        for (solution, _) in [
+            substitution(&equations).unwrap(),
+            elimination(&equations).unwrap(),
+        ] {
+            assert!(matches!(
+                solution,
+                SolutionSet::Unique(solution)
+                    if (solution["x"] - 1.0).abs() < 1e-9 && (solution["y"] - 1.0).abs() < 1e-9
+            ));
+        }
+
+        let equations = parse_standard_forms::<f64>(&[
+            "0.000000000001x + 0.000000000001y = 0.000000000001",
+            "0.000000000002x + 0.000000000002y = 0.000000000001",
+        ]);
+        for (solution, _) in [
+            substitution(&equations).unwrap(),
+            elimination(&equations).unwrap(),
+        ] {
+            assert!(matches!(solution, SolutionSet::Inconsistent { .. }));
+        }
+    }
+}
@@ -1,53 +1,173 @@
 use std::fmt::{Display, Formatter};
 
-use crate::expr::{Constant, Expr, Op};
+use ariadne::{Label, Report, ReportKind};
+use thiserror::Error;
 
-#[derive(Debug)]
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use crate::{
+    expr::{Constant, Expr, ExprKind, Op, Span},
+    scalar::Rational,
+};
+
+/// Whole powers of pronumerals are only expanded up to this exponent.
+const MAX_EXPANDED_POWER: i32 = 64;
+/// An expanded power may have at most this many terms, after combining like terms.
+const MAX_EXPANDED_TERMS: usize = 1000;
+/// Whole powers of exact numbers are only computed exactly while the result has at most about
+/// this many bits in its numerator or denominator, past it they are approximated.
+const MAX_EXACT_BITS: u64 = 1 << 16;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(
+        "Cannot divide by an expression containing a pronumeral, so the equation is not linear"
+    )]
+    PronumeralInDenominator(Span),
+    #[error("Cannot raise to a power containing a pronumeral, so the equation is not linear")]
+    PronumeralInExponent(Span),
+    #[error("Cannot raise a pronumeral to a negative or fractional power")]
+    NonIntegerPower(Span),
+    #[error("Cannot divide by zero")]
+    DivisionByZero(Span),
+    #[error("The power is too large to expand")]
+    PowerTooLarge(Span),
+    #[error("The value is not a finite real number")]
+    NotReal(Span),
+    #[error("The value is too large or too small to approximate")]
+    OutOfRange(Span),
+}
+pub type Result<T> = core::result::Result<T, Error>;
+
+impl Error {
+    /// The part of the source that caused the error.
+    pub fn span(&self) -> Span {
+        match self {
+            Error::PronumeralInDenominator(span)
+            | Error::PronumeralInExponent(span)
+            | Error::NonIntegerPower(span)
+            | Error::DivisionByZero(span)
+            | Error::PowerTooLarge(span)
+            | Error::NotReal(span)
+            | Error::OutOfRange(span) => span.clone(),
+        }
+    }
+
+    pub fn report(&self) -> Report<'static> {
+        let label = match self {
+            Error::PronumeralInDenominator(_) => "this divisor contains a pronumeral",
+            Error::PronumeralInExponent(_) => "this exponent contains a pronumeral",
+            Error::NonIntegerPower(_) => "this power is negative or fractional",
+            Error::DivisionByZero(_) => "this divisor is zero",
+            Error::PowerTooLarge(_) => "this power has too many terms to expand",
+            Error::NotReal(_) => "this has no finite real value",
+            Error::OutOfRange(_) => "this is out of range",
+        };
+        Report::build(ReportKind::Error, (), self.span().start)
+            .with_message(self)
+            .with_label(Label::new(self.span()).with_message(label))
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct TermList {
     pub(crate) terms: Vec<Term>,
 }
 
 impl Display for TermList {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            self.terms
-                .iter()
-                .map(std::string::ToString::to_string)
-                .collect::<Vec<_>>()
-                .join(" + ")
-        )
+        let Some((first, rest)) = self.terms.split_first() else {
+            return write!(f, "0");
+        };
+        write!(f, "{first}")?;
+        for term in rest {
+            if term.coeff.is_negative() {
+                let term = Term {
+                    coeff: -term.coeff.clone(),
+                    ..term.clone()
+                };
+                write!(f, " - {term}")?;
+            } else {
+                write!(f, " + {term}")?;
+            }
+        }
+        Ok(())
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Term {
-    pub coeff: f64,
-    pub pronumerals: Vec<char>,
+    pub coeff: Rational,
+    pub pronumerals: Vec<String>,
     pub constants: Vec<Constant>,
+    /// Whether `coeff` had to be rounded, because it came from a fractional power or from
+    /// dividing by π or e, so it is no longer exact.
+    pub approximate: bool,
+    /// The part of the source this term was expanded from.
+    pub span: Span,
 }
 
 impl Display for Term {
+    /// Writes the term like `2x`, leaving out a coefficient of one and bracketing fractions.
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}{}{}",
-            self.coeff,
-            self.pronumerals.iter().collect::<String>(),
-            self.constants
+        let factors = self.pronumerals.join("*")
+            + &self
+                .constants
                 .iter()
                 .map(|c| match c {
                     Constant::Pi => "π",
                     Constant::E => "e",
                 })
-                .collect::<String>()
-        )
+                .collect::<String>();
+        if factors.is_empty() {
+            return write!(f, "{}", self.coeff);
+        }
+
+        if self.coeff == -Rational::one() {
+            write!(f, "-")?;
+        } else if !self.coeff.is_integer() {
+            write!(f, "({})", self.coeff)?;
+        } else if !self.coeff.is_one() {
+            write!(f, "{}", self.coeff)?;
+        }
+        write!(f, "{factors}")
     }
 }
 impl Term {
+    fn constant(coeff: Rational, span: Span) -> Term {
+        Term {
+            coeff,
+            pronumerals: Vec::new(),
+            constants: Vec::new(),
+            approximate: false,
+            span,
+        }
+    }
+
+    /// A constant term holding the nearest fraction to `value`, which must be finite.
+    fn approximate(value: f64, span: Span) -> Result<Term> {
+        let Some(coeff) = Rational::from_float(value) else {
+            return Err(Error::NotReal(span));
+        };
+        Ok(Term {
+            approximate: true,
+            ..Term::constant(coeff, span)
+        })
+    }
+
+    fn mul(&self, other: &Term) -> Term {
+        Term {
+            coeff: &self.coeff * &other.coeff,
+            pronumerals: [self.pronumerals.as_slice(), other.pronumerals.as_slice()].concat(),
+            constants: [self.constants.as_slice(), other.constants.as_slice()].concat(),
+            approximate: self.approximate || other.approximate,
+            span: self.span.start.min(other.span.start)..self.span.end.max(other.span.end),
+        }
+    }
+
     pub fn get_approximate_coefficient(&self) -> f64 {
-        let mut num = self.coeff;
+        let mut num = self.coeff.to_f64().unwrap_or(f64::NAN);
         for constant in &self.constants {
             num *= match constant {
                 Constant::Pi => std::f64::consts::PI,
@@ -56,76 +176,296 @@ impl Term {
         }
         num
     }
+
+    /// The exact coefficient, or `None` if the term involves π or e or was rounded.
+    pub fn get_exact_coefficient(&self) -> Option<Rational> {
+        (self.constants.is_empty() && !self.approximate).then(|| self.coeff.clone())
+    }
 }
 
 impl TermList {
-    pub fn from_expr(expr: Expr) -> TermList {
-        let terms = expr.terms();
-        TermList::simplify(terms)
-    }
-
-    /// Simplifies a list of expr into a list of terms.
-    /// Note that this does not handle all cases, and is best used for simple terms like 5x or (8+3)x
-    pub fn simplify(terms: Vec<Expr>) -> TermList {
-        fn simplify_inner(
-            expr: &Expr,
-            pronumerals: &mut Vec<char>,
-            constants: &mut Vec<Constant>,
-        ) -> f64 {
-            match expr {
-                Expr::BinOp(lhs, op, rhs) => {
-                    let mut term1 = Term {
-                        coeff: 1.0,
-                        pronumerals: Vec::new(),
-                        constants: Vec::new(),
-                    };
-                    let mut term2 = Term {
-                        coeff: 1.0,
-                        pronumerals: Vec::new(),
-                        constants: Vec::new(),
-                    };
+    pub fn from_expr(expr: Expr) -> Result<TermList> {
+        Ok(TermList {
+            terms: expand(&expr)?,
+        })
+    }
 
-                    term1.coeff = simplify_inner(lhs, &mut term1.pronumerals, &mut term1.constants);
-                    term2.coeff = simplify_inner(rhs, &mut term2.pronumerals, &mut term2.constants);
-
-                    pronumerals.append(&mut term1.pronumerals);
-                    pronumerals.append(&mut term2.pronumerals);
-                    constants.append(&mut term1.constants);
-                    constants.append(&mut term2.constants);
-                    match op {
-                        Op::Mul => term1.coeff * term2.coeff,
-                        Op::Div => term1.coeff / term2.coeff,
-                        Op::Add => term1.coeff + term2.coeff,
-                        Op::Sub => term1.coeff - term2.coeff,
-                        Op::Pow => term1.coeff.powf(term2.coeff),
-                    }
+    /// Subtracts `other` from this list, by appending its terms with their coefficients negated.
+    pub fn subtract(mut self, other: TermList) -> TermList {
+        self.terms.extend(other.terms.into_iter().map(|mut term| {
+            term.coeff = -term.coeff;
+            term
+        }));
+        self
+    }
+
+    /// Combines like terms (those with the same pronumerals and constants, in any order)
+    /// and drops any term whose coefficient is zero.
+    /// A combined term keeps the span of its first occurrence.
+    pub fn canonicalise(self) -> TermList {
+        let mut terms: Vec<Term> = Vec::new();
+        for mut term in self.terms {
+            term.pronumerals.sort();
+            term.constants.sort();
+
+            match terms.iter_mut().find(|other| {
+                other.pronumerals == term.pronumerals && other.constants == term.constants
+            }) {
+                Some(other) => {
+                    other.coeff += term.coeff;
+                    other.approximate |= term.approximate;
+                }
+                None => terms.push(term),
+            }
+        }
+        terms.retain(|term| !term.coeff.is_zero());
+
+        TermList { terms }
+    }
+
+    /// Simplifies a list of expr into a list of terms, expanding each expr as in [`TermList::from_expr`].
+    pub fn simplify(terms: Vec<Expr>) -> Result<TermList> {
+        let mut new_terms = Vec::new();
+        for term in &terms {
+            new_terms.append(&mut expand(term)?);
+        }
+        Ok(TermList { terms: new_terms })
+    }
+}
+
+/// Expands an expr into a flat sum of terms.
+///
+/// Products are distributed over sums, subtracted groups are negated and sums are divided through
+/// by constant divisors. Powers of sums are expanded when the exponent is a whole number.
+fn expand(expr: &Expr) -> Result<Vec<Term>> {
+    fn has_pronumerals(terms: &[Term]) -> bool {
+        terms.iter().any(|term| !term.pronumerals.is_empty())
+    }
+    /// The value of a sum of terms without pronumerals, as a single term.
+    fn evaluate(terms: &[Term], span: Span) -> Result<Term> {
+        match terms.iter().map(Term::get_exact_coefficient).sum() {
+            Some(value) => Ok(Term::constant(value, span)),
+            None => Term::approximate(
+                terms.iter().map(Term::get_approximate_coefficient).sum(),
+                span,
+            ),
+        }
+    }
+    /// Expands `terms^whole` by repeated multiplication, which keeps π and e exact. Like terms
+    /// are combined as it goes, so only a bounded number are kept.
+    fn power(terms: &[Term], whole: i32, span: &Span) -> Result<Vec<Term>> {
+        let mut acc = vec![Term::constant(Rational::one(), span.clone())];
+        for _ in 0..whole {
+            acc = TermList {
+                terms: product(&acc, terms),
+            }
+            .canonicalise()
+            .terms;
+            if acc.len() > MAX_EXPANDED_TERMS {
+                return Err(Error::PowerTooLarge(span.clone()));
+            }
+        }
+        Ok(acc)
+    }
+    fn product(lhs: &[Term], rhs: &[Term]) -> Vec<Term> {
+        lhs.iter()
+            .flat_map(|a| rhs.iter().map(move |b| a.mul(b)))
+            .collect()
+    }
+
+    match &expr.kind {
+        ExprKind::BinOp(lhs_expr, op, rhs_expr) => {
+            let mut lhs = expand(lhs_expr)?;
+            let mut rhs = expand(rhs_expr)?;
+            match op {
+                Op::Add => {
+                    lhs.append(&mut rhs);
+                    Ok(lhs)
                 }
-                Expr::Num(num) => *num,
-                Expr::Var(c) => {
-                    pronumerals.push(*c);
-                    1.0
+                Op::Sub => {
+                    lhs.extend(rhs.into_iter().map(|mut term| {
+                        term.coeff = -term.coeff;
+                        term
+                    }));
+                    Ok(lhs)
                 }
-                Expr::Constant(constant) => {
-                    constants.push(constant.clone());
-                    1.0
+                Op::Mul => Ok(product(&lhs, &rhs)),
+                Op::Div => {
+                    if has_pronumerals(&rhs) {
+                        return Err(Error::PronumeralInDenominator(rhs_expr.span.clone()));
+                    }
+                    let divisor = evaluate(&rhs, rhs_expr.span.clone())?;
+                    if divisor.coeff.is_zero() {
+                        return Err(Error::DivisionByZero(rhs_expr.span.clone()));
+                    }
+                    for term in &mut lhs {
+                        term.coeff /= &divisor.coeff;
+                        term.approximate |= divisor.approximate;
+                    }
+                    Ok(lhs)
                 }
-                Expr::Equation(_, _) => {
-                    panic!("Cannot handle equation")
+                Op::Pow => {
+                    if has_pronumerals(&rhs) {
+                        return Err(Error::PronumeralInExponent(rhs_expr.span.clone()));
+                    }
+                    let exponent = evaluate(&rhs, rhs_expr.span.clone())?;
+                    let whole = exponent
+                        .get_exact_coefficient()
+                        .filter(Rational::is_integer)
+                        .and_then(|exponent| exponent.to_integer().to_i32());
+
+                    if has_pronumerals(&lhs) {
+                        let Some(whole) = whole.filter(|whole| *whole >= 0) else {
+                            return Err(Error::NonIntegerPower(expr.span.clone()));
+                        };
+                        if whole > MAX_EXPANDED_POWER {
+                            return Err(Error::PowerTooLarge(expr.span.clone()));
+                        }
+                        return power(&lhs, whole, &expr.span);
+                    }
+
+                    let base = evaluate(&lhs, lhs_expr.span.clone())?;
+                    // The bits in the numerator and denominator multiply with the exponent.
+                    let fits = |base: &Rational, whole: i32| {
+                        let bits = base.numer().bits().max(base.denom().bits());
+                        bits.saturating_mul(u64::from(whole.unsigned_abs())) <= MAX_EXACT_BITS
+                    };
+                    match (base.get_exact_coefficient(), whole) {
+                        (Some(base), Some(whole)) if fits(&base, whole) => {
+                            if base.is_zero() && whole < 0 {
+                                return Err(Error::DivisionByZero(expr.span.clone()));
+                            }
+                            Ok(vec![Term::constant(base.pow(whole), expr.span.clone())])
+                        }
+                        (None, Some(whole)) if (0..=MAX_EXPANDED_POWER).contains(&whole) => {
+                            power(&lhs, whole, &expr.span)
+                        }
+                        _ => {
+                            let exponent = exponent.get_approximate_coefficient();
+                            if base.coeff.is_zero() && exponent < 0.0 {
+                                return Err(Error::DivisionByZero(expr.span.clone()));
+                            }
+                            let value = base.get_approximate_coefficient().powf(exponent);
+                            if value.is_infinite() || (value == 0.0 && !base.coeff.is_zero()) {
+                                return Err(Error::OutOfRange(expr.span.clone()));
+                            }
+                            Ok(vec![Term::approximate(value, expr.span.clone())?])
+                        }
+                    }
                 }
             }
         }
-        let mut new_terms = Vec::new();
-        for term in &terms {
-            let mut pronumerals = Vec::new();
-            let mut constants = Vec::new();
-            let coeff = simplify_inner(term, &mut pronumerals, &mut constants);
-
-            new_terms.push(Term {
-                coeff,
-                pronumerals,
-                constants,
-            });
+        ExprKind::Num(num) => Ok(vec![Term::constant(num.clone(), expr.span.clone())]),
+        ExprKind::Var(c) => Ok(vec![Term {
+            pronumerals: vec![c.clone()],
+            ..Term::constant(Rational::one(), expr.span.clone())
+        }]),
+        ExprKind::Constant(constant) => Ok(vec![Term {
+            constants: vec![constant.clone()],
+            ..Term::constant(Rational::one(), expr.span.clone())
+        }]),
+        ExprKind::Equation(_, _) => {
+            panic!("Cannot handle equation")
         }
-        TermList { terms: new_terms }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::parse_equations;
+
+    /// Moves every term of an equation to the left and combines like terms.
+    fn expand_equation(source: &str) -> Result<TermList> {
+        let ExprKind::Equation(lhs, rhs) = parse_equations(&[source]).remove(0).kind else {
+            unreachable!("parse only returns equations")
+        };
+        let lhs = TermList::from_expr(*lhs)?;
+        let rhs = TermList::from_expr(*rhs)?;
+        Ok(lhs.subtract(rhs).canonicalise())
+    }
+
+    #[test]
+    fn distribution() {
+        assert_eq!(
+            expand_equation("x - (y + z) = 0").unwrap().to_string(),
+            "x - y - z"
+        );
+        assert_eq!(
+            expand_equation("2(x - 3y) = 0").unwrap().to_string(),
+            "2x - 6y"
+        );
+    }
+
+    #[test]
+    fn whole_powers() {
+        assert_eq!(
+            expand_equation("(x + 1)^2 - x^2 = 3").unwrap().to_string(),
+            "2x - 2"
+        );
+        assert_eq!(
+            expand_equation("x^0 + y = 2").unwrap().to_string(),
+            "-1 + y"
+        );
+        assert_eq!(
+            expand_equation("(x + y + z)^25 = 1").unwrap().terms.len(),
+            352
+        );
+    }
+
+    #[test]
+    fn large_powers() {
+        assert!(matches!(
+            expand_equation("x^100000000 = 1"),
+            Err(Error::PowerTooLarge(_))
+        ));
+        assert!(matches!(
+            expand_equation("(x + y + z + w)^40 = 1"),
+            Err(Error::PowerTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn constant_powers() {
+        assert_eq!(
+            expand_equation("2^10 x = 1024").unwrap().to_string(),
+            "1024x - 1024"
+        );
+        assert_eq!(
+            expand_equation("2^(-2) x = 1").unwrap().to_string(),
+            "(1/4)x - 1"
+        );
+        assert!(matches!(
+            expand_equation("2^100000000 x = 1"),
+            Err(Error::OutOfRange(_))
+        ));
+        assert!(matches!(
+            expand_equation("2^(0-2147483648) x = 1"),
+            Err(Error::OutOfRange(_))
+        ));
+        assert!(matches!(
+            expand_equation("2^4096^4096^4096 x = 1"),
+            Err(Error::OutOfRange(_))
+        ));
+        assert_eq!(
+            expand_equation("2^1000 x = 2^1001").unwrap().to_string(),
+            format!(
+                "{}x - {}",
+                Rational::from_integer(2.into()).pow(1000),
+                Rational::from_integer(2.into()).pow(1001)
+            )
+        );
+        assert!(matches!(
+            expand_equation("0^(-1) + x = 1"),
+            Err(Error::DivisionByZero(_))
+        ));
+    }
+
+    #[test]
+    fn not_real() {
+        assert!(matches!(
+            expand_equation("(-8)^(1/3) x + y = 1"),
+            Err(Error::NotReal(_))
+        ));
     }
 }
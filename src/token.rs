@@ -1,39 +1,10 @@
 use std::fmt::Display;
-use std::hash::{Hash, Hasher};
-use std::str::FromStr;
+use std::ops::Range;
 
 use logos::Logos;
+use num_bigint::BigInt;
 
-#[derive(Debug, Clone)]
-pub struct Float(pub f64);
-
-impl Hash for Float {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.0.to_string().hash(state);
-    }
-}
-
-impl PartialEq for Float {
-    fn eq(&self, other: &Self) -> bool {
-        self.0.total_cmp(&other.0).is_eq()
-    }
-}
-
-impl FromStr for Float {
-    type Err = <f64 as FromStr>::Err;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Float(s.parse()?))
-    }
-}
-
-impl Display for Float {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.0.fmt(f)
-    }
-}
-
-impl Eq for Float {}
+use crate::scalar::Rational;
 
 #[derive(Debug, Logos, Clone, PartialEq, Eq, Hash)]
 pub enum Token {
@@ -45,11 +16,14 @@ pub enum Token {
     E,
 
     // Values
-    #[regex("[0-9]+", | lex | lex.slice().parse().ok())]
-    #[regex("[0-9]+\\.[0-9]+", | lex | lex.slice().parse().ok())]
-    Number(Float),
-    #[regex("[a-zA-Z]", | lex | lex.slice().chars().next().expect("There should be something"))]
-    Pronumeral(char),
+    #[regex("[0-9]+", | lex | parse_decimal(lex.slice()))]
+    #[regex("[0-9]+\\.[0-9]+", | lex | parse_decimal(lex.slice()))]
+    Number(Rational),
+    /// A variable name.
+    ///
+    /// The lexer matches whole words, which are then broken up by [`split_word`].
+    #[regex("[a-zA-Z][a-zA-Z0-9]*(_[a-zA-Z0-9]+)*", | lex | lex.slice().to_string())]
+    Pronumeral(String),
 
     // Symbols
     #[token("+")]
@@ -88,3 +62,79 @@ impl Display for Token {
         }
     }
 }
+
+/// Parses a decimal such as `12` or `0.25` into the exact fraction it represents.
+fn parse_decimal(s: &str) -> Option<Rational> {
+    let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+    let numerator = format!("{whole}{fraction}").parse::<BigInt>().ok()?;
+    let denominator = BigInt::from(10).pow(u32::try_from(fraction.len()).ok()?);
+    Some(Rational::new(numerator, denominator))
+}
+
+/// Breaks a word matched by the lexer into the names (and constants) it is made of.
+///
+/// A name is a single letter optionally followed by digits (`x`, `x1`, `y12`), so a run of
+/// letters such as `xy` is the implicit product of `x` and `y`, and `pie` is `pi` times `e`.
+/// A word containing an underscore is always a single name, which allows subscripts and longer
+/// names such as `x_2`, `I_a` and `flow_in`.
+pub fn split_word(word: &str, span: Range<usize>) -> Vec<(Token, Range<usize>)> {
+    if word.contains('_') {
+        return vec![(Token::Pronumeral(word.to_string()), span)];
+    }
+
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    while start < word.len() {
+        let end = word[start + 1..]
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(word.len(), |i| start + 1 + i);
+        pieces.push(start..end);
+        start = end;
+    }
+
+    let mut tokens = Vec::new();
+    let mut pieces = pieces.into_iter().peekable();
+    while let Some(piece) = pieces.next() {
+        let range = span.start + piece.start..span.start + piece.end;
+        let next = pieces.peek().map(|next| &word[next.clone()]);
+        match (&word[piece.clone()], next) {
+            ("p", Some("i")) | ("P", Some("I")) => {
+                pieces.next();
+                tokens.push((Token::Pi, range.start..range.end + 1));
+            }
+            ("e", _) => tokens.push((Token::E, range)),
+            (name, _) => tokens.push((Token::Pronumeral(name.to_string()), range)),
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(word: &str) -> Vec<Token> {
+        split_word(word, 0..word.len())
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect()
+    }
+
+    fn pronumeral(name: &str) -> Token {
+        Token::Pronumeral(name.to_string())
+    }
+
+    #[test]
+    fn implicit_products() {
+        assert_eq!(split("xy"), [pronumeral("x"), pronumeral("y")]);
+        assert_eq!(split("x12y"), [pronumeral("x12"), pronumeral("y")]);
+        assert_eq!(split("pie"), [Token::Pi, Token::E]);
+        assert_eq!(split_word("pix", 4..7)[1], (pronumeral("x"), 6..7));
+    }
+
+    #[test]
+    fn underscores() {
+        assert_eq!(split("x_2"), [pronumeral("x_2")]);
+        assert_eq!(split("flow_in"), [pronumeral("flow_in")]);
+    }
+}
@@ -0,0 +1,195 @@
+use std::fmt::{Display, Formatter};
+
+use thiserror::Error;
+
+use crate::{
+    eval,
+    expr::{Expr, ExprKind, Op},
+    solve::Solution,
+};
+
+/// Residuals smaller than this, relative to the size of the terms in their equation, are treated
+/// as rounding error.
+pub const TOLERANCE: f64 = 1e-9;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Expression {0} is not an equation")]
+    NotEquation(usize),
+    #[error("Equation {0} uses {1}, which the solution does not give a value for")]
+    MissingValue(usize, String),
+    #[error("Failed to evaluate equation {0}.")]
+    EvaluationFail(usize, #[source] crate::eval::Error),
+}
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// How far each equation is from being satisfied by a solution.
+#[derive(Debug, Clone)]
+pub struct Verification {
+    /// Each equation, as it was typed.
+    pub equations: Vec<String>,
+    /// `lhs - rhs` for each equation, in order.
+    pub residuals: Vec<f64>,
+    /// The size of the terms in each equation, or one if they are smaller, which the tolerance
+    /// is relative to.
+    pub scales: Vec<f64>,
+    pub tolerance: f64,
+}
+
+impl Verification {
+    /// The equations (1-based) whose residual is above the tolerance.
+    pub fn failures(&self) -> Vec<usize> {
+        (0..self.residuals.len())
+            .filter(|&i| !self.is_satisfied(i))
+            .map(|i| i + 1)
+            .collect()
+    }
+
+    /// Whether the residual of the equation at `index` is within the tolerance.
+    fn is_satisfied(&self, index: usize) -> bool {
+        self.residuals[index].abs() <= self.tolerance * self.scales[index]
+    }
+}
+
+impl Display for Verification {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, (equation, residual)) in self.equations.iter().zip(&self.residuals).enumerate() {
+            write!(f, "({}) {equation}: residual {residual:.3e}", i + 1)?;
+            if !self.is_satisfied(i) {
+                write!(f, "  <- above tolerance")?;
+            }
+            writeln!(f)?;
+        }
+        let failures = self.failures();
+        if failures.is_empty() {
+            write!(
+                f,
+                "Every equation is satisfied to within {:e} of the size of its terms.",
+                self.tolerance
+            )
+        } else {
+            write!(
+                f,
+                "{} of {} equations are not satisfied to within {:e} of the size of their terms.",
+                failures.len(),
+                self.residuals.len(),
+                self.tolerance
+            )
+        }
+    }
+}
+
+/// Substitutes `solution` into each equation, finding its residual `lhs - rhs`. `sources` are the
+/// text each equation was parsed from.
+pub fn verify(
+    exprs: &[Expr],
+    sources: &[String],
+    solution: &Solution,
+    tolerance: f64,
+) -> Result<Verification> {
+    let residuals = exprs
+        .iter()
+        .enumerate()
+        .map(|(i, expr)| residual(i + 1, expr, solution))
+        .collect::<Result<Vec<_>>>()?;
+    let scales = exprs
+        .iter()
+        .map(|expr| magnitude(expr, solution).max(1.0))
+        .collect();
+
+    Ok(Verification {
+        equations: sources
+            .iter()
+            .map(|source| source.trim().to_string())
+            .collect(),
+        residuals,
+        scales,
+        tolerance,
+    })
+}
+
+/// The size of the terms of `expr` when its pronumerals take the values in `solution`: its value
+/// if every sum were of the magnitudes of its terms. Rounding error in evaluating `expr` grows
+/// with this rather than with its value.
+///
+/// The expression must already have evaluated without error.
+fn magnitude(expr: &Expr, solution: &Solution) -> f64 {
+    let value = |expr| eval::evaluate(expr, solution).map_or(f64::NAN, f64::abs);
+    match &expr.kind {
+        ExprKind::BinOp(lhs, Op::Add | Op::Sub, rhs) | ExprKind::Equation(lhs, rhs) => {
+            magnitude(lhs, solution) + magnitude(rhs, solution)
+        }
+        ExprKind::BinOp(lhs, Op::Mul, rhs) => magnitude(lhs, solution) * magnitude(rhs, solution),
+        ExprKind::BinOp(lhs, Op::Div, rhs) => magnitude(lhs, solution) / value(rhs),
+        ExprKind::BinOp(_, Op::Pow, _)
+        | ExprKind::Num(_)
+        | ExprKind::Var(_)
+        | ExprKind::Constant(_) => value(expr),
+    }
+}
+
+/// The value of `lhs - rhs` for equation `n` when its pronumerals take the values in `solution`.
+fn residual(n: usize, expr: &Expr, solution: &Solution) -> Result<f64> {
+    if !matches!(expr.kind, ExprKind::Equation(..)) {
+        return Err(Error::NotEquation(n));
+    }
+
+    eval::evaluate(expr, solution).map_err(|err| match err {
+        eval::Error::Unbound(var, _) => Error::MissingValue(n, var),
+        err => Error::EvaluationFail(n, err),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::parse_equations;
+
+    #[test]
+    fn residuals() {
+        let sources = ["2x + 3y = 7".to_string(), "0.5x - y = 1".to_string()];
+        let exprs = parse_equations(&sources.each_ref().map(String::as_str));
+        let solution = [("x".to_string(), 2.0), ("y".to_string(), 1.5)].into();
+        let verification = verify(&exprs, &sources, &solution, TOLERANCE).unwrap();
+        assert_eq!(verification.residuals, [1.5, -1.5]);
+        assert_eq!(verification.failures(), [1, 2]);
+        assert_eq!(
+            verification.to_string(),
+            "(1) 2x + 3y = 7: residual 1.500e0  <- above tolerance\n\
+             (2) 0.5x - y = 1: residual -1.500e0  <- above tolerance\n\
+             2 of 2 equations are not satisfied to within 1e-9 of the size of their terms."
+        );
+
+        let solution = [("x".to_string(), 2.0)].into();
+        assert!(matches!(
+            verify(&exprs, &sources, &solution, TOLERANCE),
+            Err(Error::MissingValue(1, var)) if var == "y"
+        ));
+    }
+
+    #[test]
+    fn large_terms() {
+        let sources = [
+            "123456789.123x + 987654321.987y = 3.3".to_string(),
+            "0.3x - 0.7y = 1.1".to_string(),
+        ];
+        let exprs = parse_equations(&sources.each_ref().map(String::as_str));
+        let solution = [
+            ("x".to_string(), 2.838_709_689_298_212),
+            ("y".to_string(), -0.354_838_704_586_480_87),
+        ]
+        .into();
+        let verification = verify(&exprs, &sources, &solution, TOLERANCE).unwrap();
+        assert!(verification.scales[0] > 1e8);
+        assert!((verification.scales[1] - 2.2).abs() < 1e-9);
+        assert!(verification.failures().is_empty(), "{verification}");
+
+        let solution = [("x".to_string(), 2.8), ("y".to_string(), -0.35)].into();
+        assert_eq!(
+            verify(&exprs, &sources, &solution, TOLERANCE)
+                .unwrap()
+                .failures(),
+            [1, 2]
+        );
+    }
+}